@@ -0,0 +1,7 @@
+fn main() {
+    // the target triple is only known to build scripts; it is recorded in file headers
+    if let Ok(target) = std::env::var("TARGET") {
+        println!("cargo:rustc-env=ASSERT_TV_TARGET={target}");
+    }
+    println!("cargo:rerun-if-changed=build.rs");
+}
@@ -0,0 +1,342 @@
+use crate::json_path::{JsonPath, Segment};
+use crate::TestValue;
+use serde_json::Value;
+use std::fmt::{Display, Formatter};
+
+/// Values whose compact JSON rendering is longer than this are summarized in diff output.
+const MAX_RENDERED_VALUE_LEN: usize = 80;
+
+/// Number of differences listed in a mismatch message before the rest is elided.
+pub(crate) const MAX_LISTED_DIFFERENCES: usize = 32;
+
+#[derive(Debug, Clone, PartialEq)]
+/// A single difference between a loaded and an observed JSON value.
+///
+/// Paths use a JSON-path like notation rooted at `$`, e.g. `$.items[3].price`.
+pub enum ValueDifference {
+    /// The value at `path` exists on both sides but differs.
+    Changed {
+        path: String,
+        loaded: Value,
+        observed: Value,
+    },
+    /// The object key or array element at `path` only exists in the observed value.
+    Added { path: String, observed: Value },
+    /// The object key or array element at `path` only exists in the loaded value.
+    Removed { path: String, loaded: Value },
+}
+
+impl ValueDifference {
+    pub fn path(&self) -> &str {
+        match self {
+            ValueDifference::Changed { path, .. }
+            | ValueDifference::Added { path, .. }
+            | ValueDifference::Removed { path, .. } => path,
+        }
+    }
+}
+
+impl Display for ValueDifference {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValueDifference::Changed {
+                path,
+                loaded,
+                observed,
+            } => write!(f, "{}: {} -> {}", path, render(loaded), render(observed)),
+            ValueDifference::Added { path, observed } => {
+                write!(f, "{}: added {}", path, render(observed))
+            }
+            ValueDifference::Removed { path, loaded } => {
+                write!(f, "{}: removed {}", path, render(loaded))
+            }
+        }
+    }
+}
+
+/// Per-field settings deciding when two stored values are considered equal.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CompareOptions {
+    tolerance: Option<f64>,
+    rel_tolerance: Option<f64>,
+}
+
+impl CompareOptions {
+    pub(crate) fn of<O>(test_vec_field: &TestValue<O>) -> Self {
+        Self {
+            tolerance: test_vec_field.tolerance,
+            rel_tolerance: test_vec_field.rel_tolerance,
+        }
+    }
+
+    fn numbers_match(&self, loaded: f64, observed: f64) -> bool {
+        let difference = (loaded - observed).abs();
+        self.tolerance
+            .is_some_and(|tolerance| difference <= tolerance)
+            || self.rel_tolerance.is_some_and(|rel_tolerance| {
+                difference <= rel_tolerance * loaded.abs().max(observed.abs())
+            })
+    }
+}
+
+/// Structurally compare two JSON values and list every difference.
+///
+/// Objects are compared key by key and arrays element by element; unchanged regions
+/// are not reported at all. Returns an empty list if both values are equal.
+pub(crate) fn diff_values(
+    loaded: &Value,
+    observed: &Value,
+    options: &CompareOptions,
+) -> Vec<ValueDifference> {
+    let mut differences = Vec::new();
+    walk(
+        &mut String::from("$"),
+        loaded,
+        observed,
+        options,
+        &mut differences,
+    );
+    differences
+}
+
+fn walk(
+    path: &mut String,
+    loaded: &Value,
+    observed: &Value,
+    options: &CompareOptions,
+    out: &mut Vec<ValueDifference>,
+) {
+    if loaded == observed {
+        return;
+    }
+    let path_len = path.len();
+    match (loaded, observed) {
+        (Value::Number(l), Value::Number(o))
+            if l.as_f64()
+                .zip(o.as_f64())
+                .is_some_and(|(l, o)| options.numbers_match(l, o)) => {}
+        (Value::Object(l), Value::Object(o)) => {
+            for (key, l_value) in l {
+                push_key(path, key);
+                match o.get(key) {
+                    Some(o_value) => walk(path, l_value, o_value, options, out),
+                    None => out.push(ValueDifference::Removed {
+                        path: path.clone(),
+                        loaded: l_value.clone(),
+                    }),
+                }
+                path.truncate(path_len);
+            }
+            for (key, o_value) in o.iter().filter(|(key, _)| !l.contains_key(*key)) {
+                push_key(path, key);
+                out.push(ValueDifference::Added {
+                    path: path.clone(),
+                    observed: o_value.clone(),
+                });
+                path.truncate(path_len);
+            }
+        }
+        (Value::Array(l), Value::Array(o)) => {
+            for index in 0..l.len().max(o.len()) {
+                path.push_str(&format!("[{}]", index));
+                match (l.get(index), o.get(index)) {
+                    (Some(l_value), Some(o_value)) => walk(path, l_value, o_value, options, out),
+                    (Some(l_value), None) => out.push(ValueDifference::Removed {
+                        path: path.clone(),
+                        loaded: l_value.clone(),
+                    }),
+                    (None, Some(o_value)) => out.push(ValueDifference::Added {
+                        path: path.clone(),
+                        observed: o_value.clone(),
+                    }),
+                    (None, None) => unreachable!("index is below the length of one array"),
+                }
+                path.truncate(path_len);
+            }
+        }
+        _ => out.push(ValueDifference::Changed {
+            path: path.clone(),
+            loaded: loaded.clone(),
+            observed: observed.clone(),
+        }),
+    }
+}
+
+fn push_key(path: &mut String, key: &str) {
+    if !key.is_empty() && key.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        path.push('.');
+        path.push_str(key);
+    } else {
+        path.push_str(&format!("[{:?}]", key));
+    }
+}
+
+/// Placeholder stored in place of ignored output regions if no `redact` text is configured.
+pub(crate) const DEFAULT_REDACTION: &str = "<ignored>";
+
+/// Replace every part of `value` selected by `paths` with the `placeholder` string.
+pub(crate) fn mask_paths(value: &mut Value, paths: &[JsonPath], placeholder: &str) {
+    mask_at(&mut Vec::new(), value, paths, placeholder);
+}
+
+fn mask_at(location: &mut Vec<Segment>, value: &mut Value, paths: &[JsonPath], placeholder: &str) {
+    if paths.iter().any(|path| path.matches(location)) {
+        *value = Value::String(placeholder.to_string());
+        return;
+    }
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                location.push(Segment::Key(key.clone()));
+                mask_at(location, value, paths, placeholder);
+                location.pop();
+            }
+        }
+        Value::Array(items) => {
+            for (index, item) in items.iter_mut().enumerate() {
+                location.push(Segment::Index(index));
+                mask_at(location, item, paths, placeholder);
+                location.pop();
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Sort the arrays selected by `paths` (every array if `paths` is empty) into a canonical
+/// order, so values that only differ in the order of set-like collections are equal.
+pub(crate) fn sort_unordered_arrays(value: &mut Value, paths: &[JsonPath]) {
+    sort_arrays_at(&mut Vec::new(), value, paths);
+}
+
+fn sort_arrays_at(location: &mut Vec<Segment>, value: &mut Value, paths: &[JsonPath]) {
+    // normalize nested values first, so equal elements also render equally
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                location.push(Segment::Key(key.clone()));
+                sort_arrays_at(location, value, paths);
+                location.pop();
+            }
+        }
+        Value::Array(items) => {
+            for (index, item) in items.iter_mut().enumerate() {
+                location.push(Segment::Index(index));
+                sort_arrays_at(location, item, paths);
+                location.pop();
+            }
+            if paths.is_empty() || paths.iter().any(|path| path.matches(location)) {
+                items.sort_by_cached_key(Value::to_string);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Compact rendering of a value, summarizing large arrays, objects and strings.
+fn render(value: &Value) -> String {
+    let rendered = value.to_string();
+    if rendered.chars().count() <= MAX_RENDERED_VALUE_LEN {
+        return rendered;
+    }
+    match value {
+        Value::Array(items) => format!("[… {} items]", items.len()),
+        Value::Object(map) => format!("{{… {} keys}}", map.len()),
+        Value::String(s) => {
+            let prefix: String = s.chars().take(MAX_RENDERED_VALUE_LEN / 2).collect();
+            format!("{:?}… ({} chars)", prefix, s.chars().count())
+        }
+        _ => rendered,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff_values, mask_paths, sort_unordered_arrays, CompareOptions, ValueDifference};
+    use crate::json_path::JsonPath;
+    use serde_json::json;
+
+    #[test]
+    fn diff_reports_paths() {
+        let loaded = json!({"items": [{"price": 10}, {"price": 5}], "old": 1, "same": [1, 2]});
+        let observed =
+            json!({"items": [{"price": 11}, {"price": 5}, 7], "new key": true, "same": [1, 2]});
+        let rendered: Vec<String> = diff_values(&loaded, &observed, &CompareOptions::default())
+            .iter()
+            .map(ValueDifference::to_string)
+            .collect();
+        assert_eq!(
+            rendered,
+            vec![
+                "$.items[0].price: 10 -> 11",
+                "$.items[2]: added 7",
+                "$.old: removed 1",
+                "$[\"new key\"]: added true",
+            ]
+        );
+        assert!(diff_values(&loaded, &loaded, &CompareOptions::default()).is_empty());
+    }
+
+    #[test]
+    fn diff_summarizes_large_values() {
+        let loaded = json!((0..1000).collect::<Vec<_>>());
+        let observed = json!({"replaced": "x".repeat(200)});
+        let rendered = diff_values(&loaded, &observed, &CompareOptions::default())[0].to_string();
+        assert!(rendered.starts_with("$: [… 1000 items] -> "), "{rendered}");
+        assert!(rendered.len() < 100, "{rendered}");
+    }
+
+    #[test]
+    fn diff_applies_tolerances_to_nested_numbers() {
+        let loaded = json!({"x": [1.0, 100.0], "n": 3});
+        let observed = json!({"x": [1.0 + 1e-12, 100.0 + 1e-7], "n": 3});
+        let absolute = CompareOptions {
+            tolerance: Some(1e-9),
+            ..Default::default()
+        };
+        assert_eq!(
+            diff_values(&loaded, &observed, &absolute)
+                .iter()
+                .map(ValueDifference::path)
+                .collect::<Vec<_>>(),
+            vec!["$.x[1]"]
+        );
+        let relative = CompareOptions {
+            rel_tolerance: Some(1e-8),
+            ..Default::default()
+        };
+        assert!(diff_values(&loaded, &observed, &relative).is_empty());
+        assert_eq!(
+            diff_values(&json!(1.0), &json!(1.1), &relative).len(),
+            1,
+            "differences beyond the tolerance are still reported"
+        );
+    }
+
+    #[test]
+    fn unordered_arrays_are_sorted() {
+        let mut all = json!({"a": [3, 1, 2], "b": [[2, 1], [0]]});
+        sort_unordered_arrays(&mut all, &[]);
+        assert_eq!(all, json!({"a": [1, 2, 3], "b": [[0], [1, 2]]}));
+
+        let mut selected = json!({"a": [3, 1, 2], "b": [[2, 1], [0]]});
+        sort_unordered_arrays(&mut selected, &[JsonPath::parse("$.b[*]").unwrap()]);
+        assert_eq!(selected, json!({"a": [3, 1, 2], "b": [[1, 2], [0]]}));
+    }
+
+    #[test]
+    fn masked_paths_are_replaced() {
+        let mut value = json!({"id": 7, "meta": {"ts": 1, "v": 2}, "runs": [{"ts": 3}, {"ts": 4}]});
+        let paths = [
+            JsonPath::parse("$.id").unwrap(),
+            JsonPath::parse("$.meta.ts").unwrap(),
+            JsonPath::parse("$.runs[*].ts").unwrap(),
+            JsonPath::parse("$.missing").unwrap(),
+        ];
+        mask_paths(&mut value, &paths, "x");
+        assert_eq!(
+            value,
+            json!({"id": "x", "meta": {"ts": "x", "v": 2}, "runs": [{"ts": "x"}, {"ts": "x"}]})
+        );
+    }
+}
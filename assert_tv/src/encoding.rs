@@ -0,0 +1,103 @@
+//! Text encodings for byte fields, selected with `#[test_vec(encoding = "hex" | "base64")]`.
+//!
+//! Byte buffers such as `Vec<u8>` or `[u8; 32]` serialize to JSON arrays of integers by
+//! default. These helpers store them as a single string instead; they are used by the
+//! serializer/deserializer pair generated by the derive macro, but can also be passed to
+//! `serialize_with`/`deserialize_with` directly.
+
+fn bytes_into<T: TryFrom<Vec<u8>>>(bytes: Vec<u8>) -> anyhow::Result<T> {
+    let len = bytes.len();
+    T::try_from(bytes).map_err(|_| {
+        anyhow::anyhow!(
+            "{} decoded bytes do not fit `{}`",
+            len,
+            std::any::type_name::<T>()
+        )
+    })
+}
+
+fn expect_str(value: &serde_json::Value) -> anyhow::Result<&str> {
+    value
+        .as_str()
+        .ok_or_else(|| anyhow::anyhow!("expected an encoded string, found `{}`", value))
+}
+
+/// Lowercase hexadecimal strings, e.g. `"00ff"`; upper case is accepted when decoding.
+pub mod hex {
+    use super::{bytes_into, expect_str};
+
+    pub fn serialize<T: AsRef<[u8]>>(value: &T) -> anyhow::Result<serde_json::Value> {
+        let encoded: String = value
+            .as_ref()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        Ok(serde_json::Value::String(encoded))
+    }
+
+    pub fn deserialize<T: TryFrom<Vec<u8>>>(value: &serde_json::Value) -> anyhow::Result<T> {
+        let encoded = expect_str(value)?;
+        if encoded.len() % 2 != 0 {
+            anyhow::bail!("hex string `{}` has an odd length", encoded);
+        }
+        let bytes = (0..encoded.len())
+            .step_by(2)
+            .map(|i| {
+                encoded
+                    .get(i..i + 2)
+                    // `from_str_radix` accepts a leading sign
+                    .filter(|digits| digits.bytes().all(|b| b.is_ascii_hexdigit()))
+                    .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+                    .ok_or_else(|| anyhow::anyhow!("invalid hex string `{}`", encoded))
+            })
+            .collect::<anyhow::Result<Vec<u8>>>()?;
+        bytes_into(bytes)
+    }
+}
+
+/// Standard, padded base64 strings.
+pub mod base64 {
+    use super::{bytes_into, expect_str};
+    use ::base64::prelude::{Engine, BASE64_STANDARD};
+
+    pub fn serialize<T: AsRef<[u8]>>(value: &T) -> anyhow::Result<serde_json::Value> {
+        Ok(serde_json::Value::String(
+            BASE64_STANDARD.encode(value.as_ref()),
+        ))
+    }
+
+    pub fn deserialize<T: TryFrom<Vec<u8>>>(value: &serde_json::Value) -> anyhow::Result<T> {
+        let bytes = BASE64_STANDARD.decode(expect_str(value)?)?;
+        bytes_into(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{base64, hex};
+    use serde_json::json;
+
+    #[test]
+    fn round_trip() {
+        let key: [u8; 4] = [0x00, 0x7f, 0xab, 0xff];
+        assert_eq!(hex::serialize(&key).unwrap(), json!("007fabff"));
+        assert_eq!(
+            hex::deserialize::<[u8; 4]>(&json!("007FABFF")).unwrap(),
+            key
+        );
+        assert_eq!(base64::serialize(&key).unwrap(), json!("AH+r/w=="));
+        assert_eq!(
+            base64::deserialize::<Vec<u8>>(&json!("AH+r/w==")).unwrap(),
+            key.to_vec()
+        );
+
+        assert!(hex::deserialize::<Vec<u8>>(&json!("abc")).is_err());
+        assert!(hex::deserialize::<Vec<u8>>(&json!("zz")).is_err());
+        assert!(hex::deserialize::<Vec<u8>>(&json!("+f")).is_err());
+        assert!(hex::deserialize::<Vec<u8>>(&json!("00+f")).is_err());
+        assert!(hex::deserialize::<Vec<u8>>(&json!("0")).is_err());
+        assert!(hex::deserialize::<Vec<u8>>(&json!([1, 2])).is_err());
+        assert!(hex::deserialize::<[u8; 2]>(&json!("007fab")).is_err());
+        assert!(base64::deserialize::<Vec<u8>>(&json!("AH+r/w")).is_err());
+    }
+}
@@ -0,0 +1,388 @@
+use crate::diff::MAX_LISTED_DIFFERENCES;
+use crate::{TestVectorEntryType, TestVectorFileFormat, ValueDifference};
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+/// A loaded entry that was never replayed by the code under test.
+pub struct UnconsumedEntry {
+    /// Position of the entry in the loaded test vector file.
+    pub index: usize,
+    pub name: Option<String>,
+    pub entry_type: TestVectorEntryType,
+    pub code_location: Option<String>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+/// Digest and uncompressed length of an offloaded value.
+pub struct OffloadDigest {
+    /// Hex encoded blake3 digest.
+    pub hash: String,
+    /// Length in bytes; not recorded by older test vector files.
+    pub len: Option<u64>,
+}
+
+#[derive(Debug)]
+/// Error returned by the test-vector session functions.
+///
+/// Lets harnesses classify failures, e.g. to tell a changed output value apart from a
+/// missing or unreadable test vector file. New variants may be added without a major
+/// version bump, so matches need a wildcard arm.
+#[non_exhaustive]
+pub enum TestVectorError {
+    /// No test-vector session is active.
+    NotInitialized,
+    /// A session was initialized while another session is still active.
+    AlreadyInitialized { active_file: PathBuf },
+    /// Reading, writing or creating a file failed.
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// The test vector file does not exist and `TEST_VECTORS_CI` forbids creating it.
+    FileCreationForbidden { path: PathBuf },
+    /// The format of a test vector file cannot be inferred from its extension.
+    UnknownFormat { path: PathBuf },
+    /// The session options request streaming for a file or mode that cannot be streamed.
+    StreamingUnsupported { path: PathBuf, reason: String },
+    /// The test vector file was written with a newer, unknown schema.
+    UnsupportedSchema {
+        path: PathBuf,
+        schema_version: u32,
+        generator_version: String,
+    },
+    /// A test vector file could not be parsed in the expected format.
+    Parse {
+        path: PathBuf,
+        format: TestVectorFileFormat,
+        message: String,
+    },
+    /// A test vector could not be encoded in the requested format.
+    Encode {
+        path: PathBuf,
+        format: TestVectorFileFormat,
+        message: String,
+    },
+    /// The sidecar file of an offloaded entry does not exist or cannot be opened.
+    OffloadMissing {
+        entry_index: usize,
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// The sidecar file of an offloaded entry exists but cannot be decoded.
+    OffloadCorrupted {
+        entry_index: usize,
+        path: PathBuf,
+        message: String,
+    },
+    /// The content of an offloaded value does not match the digest or length recorded in
+    /// its entry, e.g. because the blob was truncated or swapped.
+    OffloadIntegrity {
+        entry_index: usize,
+        name: Option<String>,
+        path: PathBuf,
+        expected: Box<OffloadDigest>,
+        actual: Box<OffloadDigest>,
+    },
+    /// An inline compressed value could not be decoded.
+    CompressedCorrupted {
+        entry_index: usize,
+        path: PathBuf,
+        message: String,
+    },
+    /// The observed entry has no counterpart in the loaded test vector.
+    MissingEntry {
+        name: Option<String>,
+        entry_type: TestVectorEntryType,
+        code_location: Option<String>,
+    },
+    /// The observed entry has a different name than the loaded entry.
+    NameMismatch {
+        loaded: Option<String>,
+        observed: Option<String>,
+        code_location: Option<String>,
+    },
+    /// The observed entry has a different entry type than the loaded entry.
+    TypeMismatch {
+        name: Option<String>,
+        loaded: TestVectorEntryType,
+        observed: TestVectorEntryType,
+        code_location: Option<String>,
+    },
+    /// The observed output differs from the loaded value.
+    ///
+    /// `path` points to the first difference (e.g. `$.items[3].price`), `loaded` and
+    /// `observed` are the values found at that path (`null` on the side where the key or
+    /// element is missing). `differences` lists every difference found.
+    ValueMismatch {
+        name: Option<String>,
+        code_location: Option<String>,
+        path: String,
+        loaded: Box<serde_json::Value>,
+        observed: Box<serde_json::Value>,
+        differences: Vec<ValueDifference>,
+    },
+    /// The field serializer failed on the observed value.
+    Serialize {
+        name: Option<String>,
+        source: anyhow::Error,
+    },
+    /// The field deserializer failed on a stored value.
+    Deserialize {
+        name: Option<String>,
+        source: anyhow::Error,
+    },
+    /// A JSON path configured on a field could not be parsed.
+    InvalidPath {
+        name: Option<String>,
+        path: String,
+        message: String,
+    },
+    /// `finalize_tv_case` found loaded entries that were never replayed.
+    UnconsumedEntries {
+        /// Number of entries observed during the session.
+        recorded: usize,
+        /// Number of entries in the loaded test vector file.
+        loaded: usize,
+        entries: Vec<UnconsumedEntry>,
+    },
+    /// `finalize_tv_case` found failures collected with `FailureMode::Deferred`, or several
+    /// failures otherwise.
+    DeferredFailures { failures: Vec<TestVectorError> },
+}
+
+impl Display for TestVectorError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TestVectorError::NotInitialized => write!(f, "TestEnv not initialized."),
+            TestVectorError::AlreadyInitialized { active_file } => write!(
+                f,
+                "Initialized a new test vector while a previous test vector is already initialized: {:?}",
+                active_file
+            ),
+            TestVectorError::Io { path, source } => {
+                write!(f, "Failed to access test vector file ({:?}): {}", path, source)?;
+                if source.kind() == std::io::ErrorKind::NotFound {
+                    write!(f, ". You may need to switch to init mode.")?;
+                }
+                Ok(())
+            }
+            TestVectorError::FileCreationForbidden { path } => write!(
+                f,
+                "Test vector file ({:?}) does not exist and TEST_VECTORS_CI forbids creating it. \
+                 Record it locally with TEST_MODE=init or TEST_MODE=auto and commit it.",
+                path
+            ),
+            TestVectorError::UnknownFormat { path } => write!(
+                f,
+                "Cannot infer the format of test vector file ({:?}) from its extension. \
+                 Use a known extension or pass the TestVectorFileFormat explicitly.",
+                path
+            ),
+            TestVectorError::StreamingUnsupported { path, reason } => write!(
+                f,
+                "Cannot stream test vector file ({:?}): {}",
+                path, reason
+            ),
+            TestVectorError::UnsupportedSchema {
+                path,
+                schema_version,
+                generator_version,
+            } => write!(
+                f,
+                "Test vector file ({:?}) has schema version {} (written by assert_tv {}), \
+                 but assert_tv {} only supports up to schema version {}. Upgrade assert_tv.",
+                path,
+                schema_version,
+                generator_version,
+                env!("CARGO_PKG_VERSION"),
+                crate::header::SCHEMA_VERSION
+            ),
+            TestVectorError::Parse {
+                path,
+                format,
+                message,
+            } => write!(
+                f,
+                "Failed to parse test vector file ({:?}) as {:?}: {}",
+                path, format, message
+            ),
+            TestVectorError::Encode {
+                path,
+                format,
+                message,
+            } => write!(
+                f,
+                "Failed to write test vector file ({:?}) as {:?}: {}",
+                path, format, message
+            ),
+            TestVectorError::OffloadMissing {
+                entry_index,
+                path,
+                source,
+            } => write!(
+                f,
+                "Failed to open offloaded value file ({:?}) of entry #{}: {}",
+                path, entry_index, source
+            ),
+            TestVectorError::OffloadIntegrity {
+                entry_index,
+                name,
+                path,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Offloaded value file ({:?}) of entry #{} ({:?}) failed the integrity check: \
+                 expected {}, found {}",
+                path, entry_index, name, expected, actual
+            ),
+            TestVectorError::CompressedCorrupted {
+                entry_index,
+                path,
+                message,
+            } => write!(
+                f,
+                "Failed to decode compressed value of entry #{} in {:?}: {}",
+                entry_index, path, message
+            ),
+            TestVectorError::OffloadCorrupted {
+                entry_index,
+                path,
+                message,
+            } => write!(
+                f,
+                "Failed to decode offloaded value file ({:?}) of entry #{}: {}",
+                path, entry_index, message
+            ),
+            TestVectorError::MissingEntry {
+                name,
+                entry_type,
+                code_location,
+            } => write!(
+                f,
+                "Observed value does not exist in loaded test vector:\n\
+                          observed name: {:?}\n\
+                    observed entry_type: {:?}\n\
+                          code_location: {:?}",
+                name, entry_type, code_location
+            ),
+            TestVectorError::NameMismatch {
+                loaded,
+                observed,
+                code_location,
+            } => write!(
+                f,
+                "Observed value does not match the loaded test vectors name:\n\
+                      loaded name: {:?}\n\
+                    observed name: {:?}\n\
+                    code_location: {:?}",
+                loaded, observed, code_location
+            ),
+            TestVectorError::TypeMismatch {
+                name,
+                loaded,
+                observed,
+                code_location,
+            } => write!(
+                f,
+                "Observed value does not match the loaded test vectors type:\n\
+                                 name: {:?}\n\
+                      loaded entry_type: {:?}\n\
+                    observed entry_type: {:?}\n\
+                          code_location: {:?}",
+                name, loaded, observed, code_location
+            ),
+            TestVectorError::ValueMismatch {
+                name,
+                code_location,
+                differences,
+                ..
+            } => {
+                write!(
+                    f,
+                    "Observed value does not match the loaded test vectors value:\n\
+                             name: {:?}\n\
+                    code_location: {:?}\n\
+                    differences (loaded -> observed):",
+                    name, code_location
+                )?;
+                for difference in differences.iter().take(MAX_LISTED_DIFFERENCES) {
+                    write!(f, "\n  {}", difference)?;
+                }
+                if differences.len() > MAX_LISTED_DIFFERENCES {
+                    write!(
+                        f,
+                        "\n  … and {} more differences",
+                        differences.len() - MAX_LISTED_DIFFERENCES
+                    )?;
+                }
+                Ok(())
+            }
+            TestVectorError::Serialize { name, source } => {
+                write!(f, "Failed to serialize value of {:?}: {:#}", name, source)
+            }
+            TestVectorError::Deserialize { name, source } => {
+                write!(f, "Failed to deserialize value of {:?}: {:#}", name, source)
+            }
+            TestVectorError::InvalidPath {
+                name,
+                path,
+                message,
+            } => write!(
+                f,
+                "Invalid JSON path {:?} configured for {:?}: {}",
+                path, name, message
+            ),
+            TestVectorError::UnconsumedEntries {
+                loaded, entries, ..
+            } => {
+                writeln!(
+                    f,
+                    "{} of {} loaded entries were never replayed:",
+                    entries.len(),
+                    loaded
+                )?;
+                for entry in entries {
+                    writeln!(
+                        f,
+                        "  #{} name: {:?}, entry_type: {:?}, code_location: {:?}",
+                        entry.index, entry.name, entry.entry_type, entry.code_location
+                    )?;
+                }
+                Ok(())
+            }
+            TestVectorError::DeferredFailures { failures } => {
+                let plural = if failures.len() == 1 { "" } else { "s" };
+                write!(f, "{} test vector check{} failed:", failures.len(), plural)?;
+                for (index, failure) in failures.iter().enumerate() {
+                    write!(f, "\n[{}] {}", index + 1, failure)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Display for OffloadDigest {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "blake3 {}", self.hash)?;
+        if let Some(len) = self.len {
+            write!(f, " ({} bytes)", len)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for TestVectorError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TestVectorError::Io { source, .. } | TestVectorError::OffloadMissing { source, .. } => {
+                Some(source)
+            }
+            TestVectorError::Serialize { source, .. }
+            | TestVectorError::Deserialize { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
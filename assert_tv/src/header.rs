@@ -0,0 +1,141 @@
+//! Provenance header written at the top of every test vector file.
+use crate::{TestVectorError, TestVectorFileFormat, TestVectorOptions};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Version of the test vector file layout. Files of a newer schema are rejected on load.
+pub(crate) const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub(crate) struct TestVectorHeader {
+    pub(crate) schema_version: u32,
+    /// Version of assert_tv that wrote the file.
+    pub(crate) generator_version: String,
+    /// Name and version of the package whose tests wrote the file, captured when the
+    /// tests were compiled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) package_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) package_version: Option<String>,
+    /// Target triple the tests were built for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) target: Option<String>,
+    /// User metadata from `TestVectorOptions::metadata`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) metadata: BTreeMap<String, String>,
+}
+
+impl TestVectorHeader {
+    /// The header of a file written by a session with the given options.
+    pub(crate) fn current(options: &TestVectorOptions) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            generator_version: env!("CARGO_PKG_VERSION").to_string(),
+            package_name: options.package_name.clone(),
+            package_version: options.package_version.clone(),
+            target: option_env!("ASSERT_TV_TARGET").map(str::to_string),
+            metadata: options.metadata.clone(),
+        }
+    }
+
+    /// Fails for a malformed header and for files written with a schema this version of
+    /// assert_tv does not know.
+    pub(crate) fn validate(
+        &self,
+        tv_file_path: &Path,
+        format: TestVectorFileFormat,
+    ) -> Result<(), TestVectorError> {
+        let invalid = |message: String| TestVectorError::Parse {
+            path: tv_file_path.to_path_buf(),
+            format,
+            message: format!("invalid header: {}", message),
+        };
+        if self.schema_version == 0 {
+            return Err(invalid("`schema_version` must be at least 1".to_string()));
+        }
+        if !is_version(&self.generator_version) {
+            return Err(invalid(format!(
+                "`generator_version` {:?} is not a version",
+                self.generator_version
+            )));
+        }
+        if self.schema_version > SCHEMA_VERSION {
+            return Err(TestVectorError::UnsupportedSchema {
+                path: tv_file_path.to_path_buf(),
+                schema_version: self.schema_version,
+                generator_version: self.generator_version.clone(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Whether `version` is a semantic version such as `0.6.5` or `1.0.0-rc.1`.
+fn is_version(version: &str) -> bool {
+    let core = version.split(['-', '+']).next().unwrap_or_default();
+    let numbers: Vec<&str> = core.split('.').collect();
+    numbers.len() == 3
+        && numbers
+            .iter()
+            .all(|number| !number.is_empty() && number.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// The first line of a JSON Lines file, holding the header.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct HeaderLine {
+    pub(crate) header: TestVectorHeader,
+}
+
+impl HeaderLine {
+    /// The validated header of the first line of a JSON Lines file, `None` if the line is
+    /// no header line.
+    ///
+    /// The line is recognized by its `header` key, so a malformed header fails to parse
+    /// instead of being read as an entry.
+    pub(crate) fn parse(
+        line: &str,
+        line_number: usize,
+        tv_file_path: &Path,
+    ) -> Result<Option<TestVectorHeader>, TestVectorError> {
+        let Ok(serde_json::Value::Object(mut fields)) = serde_json::from_str(line) else {
+            return Ok(None);
+        };
+        let Some(header) = fields.remove("header") else {
+            return Ok(None);
+        };
+        let header: TestVectorHeader =
+            serde_json::from_value(header).map_err(|e| TestVectorError::Parse {
+                path: tv_file_path.to_path_buf(),
+                format: TestVectorFileFormat::JsonLines,
+                message: format!("line {}: invalid header: {}", line_number, e),
+            })?;
+        header.validate(tv_file_path, TestVectorFileFormat::JsonLines)?;
+        Ok(Some(header))
+    }
+
+    /// Fails for a header line after the first line of a JSON Lines file.
+    pub(crate) fn reject_misplaced(
+        line: &str,
+        line_number: usize,
+        tv_file_path: &Path,
+    ) -> Result<(), TestVectorError> {
+        // cheap pre-check, entries rarely mention a `header` key
+        if !line.contains("\"header\"") {
+            return Ok(());
+        }
+        match serde_json::from_str(line) {
+            Ok(serde_json::Value::Object(fields)) if fields.contains_key("header") => {
+                Err(TestVectorError::Parse {
+                    path: tv_file_path.to_path_buf(),
+                    format: TestVectorFileFormat::JsonLines,
+                    message: format!(
+                        "line {}: a header is only allowed on the first line",
+                        line_number
+                    ),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+}
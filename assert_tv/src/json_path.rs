@@ -0,0 +1,105 @@
+//! Minimal JSON-path subset used to select parts of serialized test vector values.
+//!
+//! Supported syntax: the root `$`, object keys as `.key` or `["key"]`, array indices
+//! as `[3]` and wildcards as `[*]` or `.*`, e.g. `$.items[*].tags`.
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Selector {
+    Key(String),
+    Index(usize),
+    Wildcard,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct JsonPath {
+    selectors: Vec<Selector>,
+}
+
+impl JsonPath {
+    pub(crate) fn parse(path: &str) -> Result<Self, String> {
+        let mut rest = path
+            .trim()
+            .strip_prefix('$')
+            .ok_or_else(|| "path must start with `$`".to_string())?;
+        let mut selectors = Vec::new();
+        while !rest.is_empty() {
+            if let Some(after_dot) = rest.strip_prefix('.') {
+                let end = after_dot.find(['.', '[']).unwrap_or(after_dot.len());
+                let key = &after_dot[..end];
+                selectors.push(match key {
+                    "" => return Err("empty key after `.`".to_string()),
+                    "*" => Selector::Wildcard,
+                    key => Selector::Key(key.to_string()),
+                });
+                rest = &after_dot[end..];
+            } else if let Some(after_bracket) = rest.strip_prefix('[') {
+                let end = after_bracket
+                    .find(']')
+                    .ok_or_else(|| "unclosed `[`".to_string())?;
+                let inner = after_bracket[..end].trim();
+                selectors.push(if inner == "*" {
+                    Selector::Wildcard
+                } else if let Some(key) = inner
+                    .strip_prefix('"')
+                    .and_then(|inner| inner.strip_suffix('"'))
+                {
+                    Selector::Key(key.to_string())
+                } else {
+                    Selector::Index(
+                        inner
+                            .parse()
+                            .map_err(|_| format!("invalid index `{}`", inner))?,
+                    )
+                });
+                rest = &after_bracket[end + 1..];
+            } else {
+                return Err(format!("unexpected `{}`", rest));
+            }
+        }
+        Ok(Self { selectors })
+    }
+
+    /// Whether the concrete location `segments` is selected by this path.
+    pub(crate) fn matches(&self, segments: &[Segment]) -> bool {
+        self.selectors.len() == segments.len()
+            && self
+                .selectors
+                .iter()
+                .zip(segments)
+                .all(|(selector, segment)| match (selector, segment) {
+                    (Selector::Wildcard, _) => true,
+                    (Selector::Key(key), Segment::Key(segment)) => key == segment,
+                    (Selector::Index(index), Segment::Index(segment)) => index == segment,
+                    _ => false,
+                })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{JsonPath, Segment};
+
+    #[test]
+    fn parse_and_match() {
+        let path = JsonPath::parse("$.items[*][\"the tags\"][0]").unwrap();
+        let location = [
+            Segment::Key("items".to_string()),
+            Segment::Index(7),
+            Segment::Key("the tags".to_string()),
+            Segment::Index(0),
+        ];
+        assert!(path.matches(&location));
+        assert!(!path.matches(&location[..3]));
+        assert!(JsonPath::parse("$").unwrap().matches(&[]));
+        assert!(JsonPath::parse("$.*").unwrap().matches(&location[..1]));
+        assert!(JsonPath::parse("items").is_err());
+        assert!(JsonPath::parse("$[x]").is_err());
+        assert!(JsonPath::parse("$.a[1").is_err());
+    }
+}
@@ -8,16 +8,30 @@
 
 use serde::de::DeserializeOwned;
 use serde::Serialize;
+use std::collections::BTreeMap;
 use std::env;
+use std::path::Path;
 
 mod caller_location;
+mod diff;
+pub mod encoding;
+mod error;
+mod header;
+mod json_path;
+mod offload;
 mod set;
 mod storage;
+mod stream;
 mod test_vec_impl;
 
+pub use diff::ValueDifference;
+pub use error::{OffloadDigest, TestVectorError, UnconsumedEntry};
+pub use offload::{sweep_orphaned_offloads, OffloadCodec, OffloadSweep};
+#[allow(deprecated)]
+pub use test_vec_impl::process_next_entry;
 pub use test_vec_impl::{
-    finalize_tv_case, initialize_tv_case_from_file, process_next_entry, TestVecEnv,
-    TestVectorEntryType,
+    finalize_tv_case, initialize_tv_case_from_file, initialize_tv_case_with_options,
+    process_next_value, TestVecEnv, TestVectorEntryType,
 };
 
 pub use set::{TestValue, TestVector, TestVectorActive, TestVectorNOP, TestVectorSet};
@@ -43,7 +57,14 @@ pub type DynSerializer<O> = Box<dyn Fn(&O) -> anyhow::Result<serde_json::Value>
 /// through `#[test_vec(deserialize_with = "path::to::fn")]`.
 pub type DynDeserializer<O> = Box<dyn Fn(&serde_json::Value) -> anyhow::Result<O> + 'static>;
 
-#[derive(Clone, Copy)]
+/// Erased equality check used by `TestValue<T>` to compare outputs in `Check` mode.
+///
+/// Called with the loaded and the observed value and returns whether they are considered
+/// equal. Replaces the structural comparison of the serialized values when set through
+/// `#[test_vec(compare_with = "path::to::fn")]`.
+pub type DynComparator<O> = Box<dyn Fn(&O, &O) -> bool + 'static>;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 /// File format used to read/write test vector files.
 pub enum TestVectorFileFormat {
     /// JSON file (`.json`).
@@ -52,27 +73,150 @@ pub enum TestVectorFileFormat {
     Yaml,
     /// TOML file (`.toml`).
     Toml,
+    /// RON file (`.ron`); requires the `ron` feature.
+    Ron,
+    /// JSON Lines file (`.jsonl`) with one entry per line.
+    JsonLines,
+    /// CBOR file (`.cbor`); requires the `cbor` feature.
+    Cbor,
+    /// MessagePack file (`.msgpack`); requires the `msgpack` feature.
+    MessagePack,
+    /// Inferred from the file extension with [`TestVectorFileFormat::from_path`] when the
+    /// session is initialized.
+    Auto,
+}
+
+impl TestVectorFileFormat {
+    /// The format named by the extension of `path`, e.g. `Yaml` for `case.yml`.
+    ///
+    /// Returns `None` for a missing or unknown extension.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        match path.as_ref().extension()?.to_str()? {
+            "json" => Some(TestVectorFileFormat::Json),
+            "yaml" | "yml" => Some(TestVectorFileFormat::Yaml),
+            "toml" => Some(TestVectorFileFormat::Toml),
+            "ron" => Some(TestVectorFileFormat::Ron),
+            "jsonl" => Some(TestVectorFileFormat::JsonLines),
+            "cbor" => Some(TestVectorFileFormat::Cbor),
+            "msgpack" => Some(TestVectorFileFormat::MessagePack),
+            _ => None,
+        }
+    }
+
+    /// Resolve `Auto` from the extension of `path`; other formats are returned unchanged.
+    pub(crate) fn resolve(self, path: &Path) -> Result<Self, TestVectorError> {
+        match self {
+            TestVectorFileFormat::Auto => {
+                Self::from_path(path).ok_or_else(|| TestVectorError::UnknownFormat {
+                    path: path.to_path_buf(),
+                })
+            }
+            file_format => Ok(file_format),
+        }
+    }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 /// Execution mode for test vectors.
 ///
 /// - `Init`: record observed entries and write the file if changed
 /// - `Check`: load the file and validate observed entries; constants are injected
+/// - `Update`: load the file and inject its constants like `Check`, but record the observed
+///   outputs instead of validating them and rewrite the file if changed
+/// - `InitIfMissing`: behave like `Check` if the file exists and like `Init` otherwise
+///
+/// If `TEST_VECTORS_CI` is set to `1` or `true`, sessions that would create a missing
+/// test vector file fail with `TestVectorError::FileCreationForbidden` instead.
 pub enum TestMode {
     Init,
     Check,
+    Update,
+    InitIfMissing,
+}
+
+#[derive(Clone, Debug, Default)]
+/// Session options for a test-vector case.
+///
+/// Passed to `initialize_tv_case_with_options`; `initialize_tv_case_from_file` uses
+/// the defaults.
+pub struct TestVectorOptions {
+    /// Allow a `Check` session to finish before every loaded entry was replayed.
+    ///
+    /// By default `finalize_tv_case` fails with `TestVectorError::UnconsumedEntries` when
+    /// loaded entries were never replayed by the code under test.
+    pub allow_unconsumed: bool,
+    /// How observed entries are paired with loaded entries in `Check` mode.
+    pub matching: MatchingStrategy,
+    /// When mismatching outputs fail a `Check` session.
+    pub failure_mode: FailureMode,
+    /// Offload every entry whose serialized JSON is longer than this many bytes.
+    ///
+    /// A field's own `offload_threshold` takes precedence; fields with `offload = true`
+    /// are always offloaded.
+    pub offload_threshold: Option<usize>,
+    /// Codec of offloaded values, unless a field sets its own `offload_codec`.
+    pub offload_codec: OffloadCodec,
+    /// Stream entries to and from disk instead of holding the whole vector in memory.
+    ///
+    /// `Init` sessions append each entry to `<file>.partial` as it is recorded and move it
+    /// over the file on finalize; `Check` sessions read one entry at a time. Requires
+    /// `TestVectorFileFormat::JsonLines`, `MatchingStrategy::Positional` and the `Init` or
+    /// `Check` mode.
+    pub streaming: bool,
+    /// User metadata recorded in the header of written test vector files, e.g. the seed
+    /// or scenario of a recording. Not compared when checking.
+    pub metadata: BTreeMap<String, String>,
+    /// Name of the package whose tests record the file, written to the header.
+    ///
+    /// `#[test_vec_case]` sets it to `env!("CARGO_PKG_NAME")` of the crate under test.
+    pub package_name: Option<String>,
+    /// Version of the package whose tests record the file, written to the header.
+    ///
+    /// `#[test_vec_case]` sets it to `env!("CARGO_PKG_VERSION")` of the crate under test.
+    pub package_version: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+/// When mismatching outputs fail a `Check` session.
+pub enum FailureMode {
+    /// The first mismatching `check_value` fails.
+    #[default]
+    Immediate,
+    /// Mismatching outputs are collected while execution continues with the loaded
+    /// constants; `finalize_tv_case` then fails with the complete list.
+    Deferred,
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+/// Strategy used to pair observed entries with the entries of a loaded test vector.
+pub enum MatchingStrategy {
+    /// The n-th observed entry is compared with the n-th loaded entry.
+    #[default]
+    Positional,
+    /// Entries are keyed by `(test_vec_set_code_location, name, occurrence)`, so an
+    /// entry is compared with the loaded entry of the same field set and name that was
+    /// observed equally often before. Unrelated code paths can then evolve independently.
+    Keyed,
 }
 
 impl TestMode {
-    /// Reads `TEST_MODE` ("init" | "check"). Defaults to `Check`.
+    /// Reads `TEST_MODE` ("init" | "check" | "update" | "auto"). Defaults to `Check`.
+    ///
+    /// `"auto"` selects `InitIfMissing`.
     pub fn from_environment() -> Self {
         match env::var("TEST_MODE").as_deref() {
             Ok("init") => TestMode::Init,
             Ok("check") => TestMode::Check,
+            Ok("update") => TestMode::Update,
+            Ok("auto") => TestMode::InitIfMissing,
             _ => TestMode::Check, // Default fallback
         }
     }
+
+    /// Returns `true` if `TEST_VECTORS_CI` forbids creating missing test vector files.
+    pub fn file_creation_forbidden() -> bool {
+        matches!(env::var("TEST_VECTORS_CI").as_deref(), Ok("1" | "true"))
+    }
 }
 
 /// Pluggable serializer/deserializer for a type used in test vectors.
@@ -0,0 +1,433 @@
+//! Content-addressed storage of offloaded test vector values.
+//!
+//! Offloaded values are stored as `blobs/<blake3>.<extension>` next to the test vector
+//! file, so identical values are shared between entries and test vector files of a
+//! directory. The extension names the encoding and codec, e.g. `.zst` for zstd compressed
+//! JSON or `.bin.zst` for zstd compressed raw bytes.
+use crate::test_vec_impl::TestVectorData;
+use crate::{TestVectorError, TestVectorFileFormat};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::io::{Cursor, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Name of the blob directory, relative to the directory of the test vector file.
+pub(crate) const BLOB_DIR: &str = "blobs";
+
+const DEFAULT_ZSTD_LEVEL: i32 = 15;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "codec", rename_all = "lowercase")]
+/// Compression applied to offloaded values.
+///
+/// The codec is recorded in every offloaded entry, so test vector files load regardless
+/// of the codec configured when they are checked.
+pub enum OffloadCodec {
+    /// Store the serialized JSON uncompressed.
+    None,
+    /// zstd with the given level (1-22); the default uses level 15.
+    Zstd { level: i32 },
+    /// gzip with the given level (0-9); requires the `gzip` cargo feature.
+    Gzip { level: u32 },
+}
+
+impl Default for OffloadCodec {
+    fn default() -> Self {
+        OffloadCodec::Zstd {
+            level: DEFAULT_ZSTD_LEVEL,
+        }
+    }
+}
+
+impl OffloadCodec {
+    fn extension(&self) -> &'static str {
+        match self {
+            OffloadCodec::None => "json",
+            OffloadCodec::Zstd { .. } => "zst",
+            OffloadCodec::Gzip { .. } => "gz",
+        }
+    }
+
+    pub(crate) fn encode(&self, data: Vec<u8>) -> std::io::Result<Vec<u8>> {
+        match *self {
+            OffloadCodec::None => Ok(data),
+            OffloadCodec::Zstd { level } => zstd::encode_all(Cursor::new(data), level),
+            #[cfg(feature = "gzip")]
+            OffloadCodec::Gzip { level } => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::new(level));
+                encoder.write_all(&data)?;
+                encoder.finish()
+            }
+            #[cfg(not(feature = "gzip"))]
+            OffloadCodec::Gzip { .. } => Err(gzip_unsupported()),
+        }
+    }
+
+    pub(crate) fn decode(&self, data: Vec<u8>) -> std::io::Result<Vec<u8>> {
+        match self {
+            OffloadCodec::None => Ok(data),
+            OffloadCodec::Zstd { .. } => zstd::decode_all(Cursor::new(data)),
+            #[cfg(feature = "gzip")]
+            OffloadCodec::Gzip { .. } => {
+                let mut decoded = Vec::new();
+                std::io::Read::read_to_end(
+                    &mut flate2::read::GzDecoder::new(data.as_slice()),
+                    &mut decoded,
+                )?;
+                Ok(decoded)
+            }
+            #[cfg(not(feature = "gzip"))]
+            OffloadCodec::Gzip { .. } => Err(gzip_unsupported()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+/// Representation of an offloaded value inside its blob, before compression.
+pub(crate) enum OffloadEncoding {
+    /// The value serialized as JSON.
+    #[default]
+    Json,
+    /// An array of integers in `0..=255` (e.g. a `Vec<u8>`), stored as raw bytes.
+    Bytes,
+}
+
+impl OffloadEncoding {
+    pub(crate) fn encode(&self, value: &serde_json::Value) -> Result<Vec<u8>, String> {
+        match self {
+            OffloadEncoding::Json => serde_json::to_vec(value).map_err(|e| e.to_string()),
+            OffloadEncoding::Bytes => byte_array(value)
+                .ok_or_else(|| "value is not an array of integers in 0..=255".to_string()),
+        }
+    }
+
+    pub(crate) fn decode(&self, data: &[u8]) -> Result<serde_json::Value, String> {
+        match self {
+            OffloadEncoding::Json => serde_json::from_slice(data)
+                .map_err(|e| format!("Failed to parse offloaded value as a json value: {}", e)),
+            OffloadEncoding::Bytes => Ok(serde_json::Value::from(data)),
+        }
+    }
+}
+
+/// The raw bytes of a value serializing to an array of integers in `0..=255`.
+///
+/// Decoding rebuilds the identical JSON value, so the encoding is lossless.
+pub(crate) fn byte_array(value: &serde_json::Value) -> Option<Vec<u8>> {
+    value
+        .as_array()?
+        .iter()
+        .map(|item| item.as_u64().and_then(|byte| u8::try_from(byte).ok()))
+        .collect()
+}
+
+#[cfg(not(feature = "gzip"))]
+fn gzip_unsupported() -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "gzip offload codec requires the `gzip` feature of assert_tv",
+    )
+}
+
+/// Hex encoded blake3 digest of the uncompressed serialized value.
+pub(crate) fn content_hash(serialized: &[u8]) -> String {
+    blake3::hash(serialized).to_hex().to_string()
+}
+
+pub(crate) fn blob_dir(tv_file_path: &Path) -> PathBuf {
+    tv_file_path
+        .parent()
+        .unwrap_or_else(|| Path::new(""))
+        .join(BLOB_DIR)
+}
+
+pub(crate) fn blob_file_name(hash: &str, codec: OffloadCodec, encoding: OffloadEncoding) -> String {
+    match (encoding, codec) {
+        (OffloadEncoding::Json, codec) => format!("{}.{}", hash, codec.extension()),
+        (OffloadEncoding::Bytes, OffloadCodec::None) => format!("{}.bin", hash),
+        (OffloadEncoding::Bytes, codec) => format!("{}.bin.{}", hash, codec.extension()),
+    }
+}
+
+pub(crate) fn blob_path(
+    tv_file_path: &Path,
+    hash: &str,
+    codec: OffloadCodec,
+    encoding: OffloadEncoding,
+) -> PathBuf {
+    blob_dir(tv_file_path).join(blob_file_name(hash, codec, encoding))
+}
+
+/// Sidecar path of entries written before content addressing was introduced.
+pub(crate) fn legacy_sidecar_path(tv_file_path: &Path, entry_index: usize) -> PathBuf {
+    let mut path = tv_file_path.to_path_buf();
+    if let Some(file_name) = path.file_name().map(|f| f.to_string_lossy()) {
+        let new_file_name = format!("{}_offloaded_value_{}.zstd", file_name, entry_index);
+        path.set_file_name(new_file_name);
+    }
+    path
+}
+
+/// Splits a legacy sidecar file name into the test vector file name and the entry index.
+fn parse_legacy_sidecar_name(file_name: &str) -> Option<(&str, usize)> {
+    let (tv_file_name, entry_index) = file_name
+        .strip_suffix(".zstd")?
+        .rsplit_once("_offloaded_value_")?;
+    Some((tv_file_name, entry_index.parse().ok()?))
+}
+
+fn dir_of(tv_file_path: &Path) -> &Path {
+    match tv_file_path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    }
+}
+
+/// Remove all index-named sidecar files of a test vector file.
+///
+/// Called after the file was rewritten, as rewritten files only reference blobs.
+pub(crate) fn remove_legacy_sidecars(tv_file_path: &Path) -> std::io::Result<()> {
+    let Some(tv_file_name) = tv_file_path.file_name() else {
+        return Ok(());
+    };
+    for dir_entry in std::fs::read_dir(dir_of(tv_file_path))? {
+        let dir_entry = dir_entry?;
+        let file_name = dir_entry.file_name();
+        let is_own_sidecar = file_name
+            .to_str()
+            .and_then(parse_legacy_sidecar_name)
+            .is_some_and(|(owner, _)| tv_file_name.to_str() == Some(owner));
+        if is_own_sidecar {
+            std::fs::remove_file(dir_entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Default)]
+/// Outcome of `sweep_orphaned_offloads`.
+pub struct OffloadSweep {
+    /// Removed blobs and sidecar files, sorted.
+    pub removed: Vec<PathBuf>,
+    /// Test vector files that could not be parsed, with the reason. Offloaded values they
+    /// may reference are kept.
+    pub skipped: Vec<(PathBuf, TestVectorError)>,
+}
+
+/// Offloaded values referenced by the test vector files of a directory.
+#[derive(Default)]
+struct OffloadReferences {
+    blobs: HashSet<String>,
+    /// `(test vector file name, entry index)` of index-named sidecars.
+    sidecars: HashSet<(String, usize)>,
+    /// Contents of test vector files that could not be parsed; blobs whose hash appears
+    /// in them are kept.
+    unparsed_contents: Vec<Vec<u8>>,
+    /// Names of test vector files that could not be parsed; their sidecars are kept.
+    unparsed_files: HashSet<String>,
+    /// A test vector file could not be read at all, so nothing may be removed.
+    unreadable: bool,
+    skipped: Vec<(PathBuf, TestVectorError)>,
+    /// Index-named sidecar files found in the directory.
+    sidecar_files: Vec<(PathBuf, String, usize)>,
+}
+
+impl OffloadReferences {
+    /// Parse every test vector file in `dir` (by extension).
+    fn scan(dir: &Path) -> Result<Self, TestVectorError> {
+        let mut references = Self::default();
+        for dir_entry in std::fs::read_dir(dir).map_err(io_error(dir))? {
+            let path = dir_entry.map_err(io_error(dir))?.path();
+            if !path.is_file() {
+                continue;
+            }
+            let file_name = path
+                .file_name()
+                .and_then(|f| f.to_str())
+                .unwrap_or_default()
+                .to_string();
+            if let Some((owner, entry_index)) = parse_legacy_sidecar_name(&file_name) {
+                let owner = owner.to_string();
+                references.sidecar_files.push((path, owner, entry_index));
+                continue;
+            }
+            let Some(file_format) = TestVectorFileFormat::from_path(&path) else {
+                continue;
+            };
+            match TestVectorData::parse_file(&path, file_format) {
+                Ok(tv_data) => {
+                    for (entry_index, blob_file_name) in tv_data.offload_references() {
+                        match blob_file_name {
+                            Some(blob_file_name) => references.blobs.insert(blob_file_name),
+                            None => references.sidecars.insert((file_name.clone(), entry_index)),
+                        };
+                    }
+                }
+                Err(err) => {
+                    // a file that is no test vector (or a damaged one) may still name blobs
+                    match std::fs::read(&path) {
+                        Ok(content) => references.unparsed_contents.push(content),
+                        Err(_) => references.unreadable = true,
+                    }
+                    references.unparsed_files.insert(file_name);
+                    references.skipped.push((path, err));
+                }
+            }
+        }
+        Ok(references)
+    }
+
+    fn references_blob(&self, blob_file_name: &str) -> bool {
+        let hash = blob_file_name
+            .split('.')
+            .next()
+            .unwrap_or_default()
+            .as_bytes();
+        self.unreadable
+            || self.blobs.contains(blob_file_name)
+            || self.unparsed_contents.iter().any(|content| {
+                !hash.is_empty() && content.windows(hash.len()).any(|window| window == hash)
+            })
+    }
+
+    fn references_sidecar(&self, owner: &str, entry_index: usize) -> bool {
+        self.unreadable
+            || self.unparsed_files.contains(owner)
+            || self.sidecars.contains(&(owner.to_string(), entry_index))
+    }
+}
+
+fn io_error(path: &Path) -> impl FnOnce(std::io::Error) -> TestVectorError {
+    let path = path.to_path_buf();
+    move |source| TestVectorError::Io { path, source }
+}
+
+/// Blob file names in `dir/blobs`, leaving temporary files of blobs being written alone.
+fn blob_files(dir: &Path) -> Result<Vec<PathBuf>, TestVectorError> {
+    let blob_dir = dir.join(BLOB_DIR);
+    let mut blobs = Vec::new();
+    if blob_dir.is_dir() {
+        for dir_entry in std::fs::read_dir(&blob_dir).map_err(io_error(&blob_dir))? {
+            let path = dir_entry.map_err(io_error(&blob_dir))?.path();
+            let is_blob = path
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .is_some_and(|extension| matches!(extension, "json" | "bin" | "zst" | "gz"));
+            if is_blob {
+                blobs.push(path);
+            }
+        }
+    }
+    Ok(blobs)
+}
+
+/// Remove the blobs a rewritten test vector file referenced before, unless a test vector
+/// file of its directory still references them.
+pub(crate) fn remove_stale_blobs(
+    tv_file_path: &Path,
+    previous_blobs: HashSet<String>,
+) -> Result<(), TestVectorError> {
+    if previous_blobs.is_empty() {
+        return Ok(());
+    }
+    let references = OffloadReferences::scan(dir_of(tv_file_path))?;
+    for blob_file_name in previous_blobs {
+        if references.references_blob(&blob_file_name) {
+            continue;
+        }
+        let path = blob_dir(tv_file_path).join(blob_file_name);
+        match std::fs::remove_file(&path) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            removed => removed.map_err(io_error(&path))?,
+        }
+    }
+    Ok(())
+}
+
+/// Remove offloaded values of a test vector directory that no test vector file references.
+///
+/// Parses every test vector file in `dir` (by extension) and deletes unreferenced blobs in
+/// `dir/blobs` as well as index-named sidecar files whose entry no longer is offloaded.
+/// Files that cannot be parsed are skipped and reported; blobs whose hash appears in them
+/// and their sidecars are kept, and nothing is removed if one cannot be read at all.
+///
+/// Do not run this concurrently with tests writing to the same directory, since blobs
+/// are written before the test vector file referencing them.
+pub fn sweep_orphaned_offloads<P: AsRef<Path>>(dir: P) -> Result<OffloadSweep, TestVectorError> {
+    let dir = dir.as_ref();
+    let references = OffloadReferences::scan(dir)?;
+    let mut orphaned: Vec<PathBuf> = references
+        .sidecar_files
+        .iter()
+        .filter(|(_, owner, entry_index)| !references.references_sidecar(owner, *entry_index))
+        .map(|(path, _, _)| path.clone())
+        .collect();
+    for path in blob_files(dir)? {
+        let file_name = path
+            .file_name()
+            .and_then(|f| f.to_str())
+            .unwrap_or_default();
+        if !references.references_blob(file_name) {
+            orphaned.push(path);
+        }
+    }
+    for path in &orphaned {
+        std::fs::remove_file(path).map_err(io_error(path))?;
+    }
+    orphaned.sort();
+    Ok(OffloadSweep {
+        removed: orphaned,
+        skipped: references.skipped,
+    })
+}
+
+/// Whether the blob at `path` decodes to a value of the given digest and length.
+fn blob_is_intact(path: &Path, codec: OffloadCodec, hash: &str, len: usize) -> bool {
+    std::fs::read(path)
+        .and_then(|data| codec.decode(data))
+        .is_ok_and(|decoded| decoded.len() == len && content_hash(&decoded) == hash)
+}
+
+/// Write a blob unless an intact blob with the same content exists already.
+///
+/// A truncated or corrupted blob is replaced. The blob is written to a temporary file
+/// first, so concurrently running tests that share a blob never observe a partially
+/// written file.
+pub(crate) fn write_blob(
+    path: &Path,
+    serialized: Vec<u8>,
+    hash: &str,
+    codec: OffloadCodec,
+) -> std::io::Result<()> {
+    if blob_is_intact(path, codec, hash, serialized.len()) {
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    static TMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let tmp_path = path.with_extension(format!(
+        "tmp-{}-{}",
+        std::process::id(),
+        TMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let mut tmp_file = std::fs::File::create(&tmp_path)?;
+    tmp_file.write_all(&codec.encode(serialized)?)?;
+    drop(tmp_file);
+    std::fs::rename(&tmp_path, path)
+}
+
+pub(crate) fn decompress(data: Vec<u8>) -> std::io::Result<Vec<u8>> {
+    let cursor = Cursor::new(data);
+    let decompressed = zstd::decode_all(cursor)?;
+    Ok(decompressed)
+}
+
+pub(crate) fn compress(data: Vec<u8>) -> std::io::Result<Vec<u8>> {
+    let cursor = Cursor::new(data);
+    let compressed = zstd::encode_all(cursor, DEFAULT_ZSTD_LEVEL)?;
+    Ok(compressed)
+}
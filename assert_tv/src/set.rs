@@ -1,7 +1,8 @@
 use crate::caller_location::pretty_location_file;
 use crate::TlsEnvGuard;
 use crate::{
-    initialize_tv_case_from_file, DynDeserializer, DynSerializer, TestMode, TestVectorFileFormat,
+    initialize_tv_case_from_file, DynComparator, DynDeserializer, DynSerializer, OffloadCodec,
+    TestMode, TestVectorFileFormat,
 };
 use std::fmt::{Debug, Formatter};
 use std::marker::PhantomData;
@@ -29,6 +30,35 @@ pub struct TestValue<O> {
     pub deserializer: Option<DynDeserializer<O>>,
     pub compress: bool,
     pub offload: bool,
+    /// Offload the value if its serialized JSON is longer than this many bytes,
+    /// overriding the session's `TestVectorOptions::offload_threshold`.
+    pub offload_threshold: Option<usize>,
+    /// Codec of the offloaded value, overriding the session's `TestVectorOptions::offload_codec`.
+    pub offload_codec: Option<OffloadCodec>,
+    /// Offload the value as raw bytes; it must serialize to an array of integers in `0..=255`.
+    ///
+    /// Offloaded values serializing to such an array are stored as raw bytes anyway; this
+    /// forces offloading and rejects other values.
+    pub bytes: bool,
+    /// Absolute tolerance applied to every number of an output in `Check` mode.
+    pub tolerance: Option<f64>,
+    /// Relative tolerance applied to every number of an output in `Check` mode.
+    pub rel_tolerance: Option<f64>,
+    /// Custom equality for outputs, consulted instead of comparing the serialized values.
+    ///
+    /// Replaces the structural comparison; `#[derive(TestVectorSet)]` rejects combining it with
+    /// `tolerance`, `rel_tolerance`, `unordered` or `ignore_paths`.
+    pub comparator: Option<DynComparator<O>>,
+    /// JSON paths of arrays of an output whose order is irrelevant; an empty list selects
+    /// every array.
+    ///
+    /// Selected arrays are sorted before the output is stored or compared. Constants are
+    /// replayed unchanged.
+    pub unordered: Option<Vec<String>>,
+    /// JSON paths of an output that are stored as a placeholder and skipped when checking.
+    pub ignore_paths: Vec<String>,
+    /// Placeholder stored for `ignore_paths`, defaults to `"<ignored>"`.
+    pub redact: Option<String>,
     pub _data_marker: PhantomData<O>,
 }
 
@@ -65,6 +95,54 @@ impl<O> TestValue<O> {
             deserializer,
             compress,
             offload,
+            ..Default::default()
+        }
+    }
+
+    /// Manually construct a named `TestValue` at the caller's location, with default
+    /// options. Set further options with struct update syntax:
+    /// `TestValue { offload: true, ..TestValue::named("a", serializer, deserializer) }`.
+    #[track_caller]
+    pub fn named(
+        name: impl Into<String>,
+        serializer: Option<DynSerializer<O>>,
+        deserializer: Option<DynDeserializer<O>>,
+    ) -> Self {
+        let caller_location = Location::caller();
+        Self {
+            name: Some(name.into()),
+            test_value_field_code_location: format!(
+                "{}:{}",
+                pretty_location_file(caller_location.file()),
+                caller_location.line()
+            ),
+            serializer,
+            deserializer,
+            ..Default::default()
+        }
+    }
+}
+
+/// A value without name, serializers or code location, and with every option disabled.
+impl<O> Default for TestValue<O> {
+    fn default() -> Self {
+        Self {
+            name: None,
+            description: None,
+            test_value_field_code_location: String::new(),
+            serializer: None,
+            deserializer: None,
+            compress: false,
+            offload: false,
+            offload_threshold: None,
+            offload_codec: None,
+            bytes: false,
+            tolerance: None,
+            rel_tolerance: None,
+            comparator: None,
+            unordered: None,
+            ignore_paths: Vec::new(),
+            redact: None,
             _data_marker: PhantomData,
         }
     }
@@ -80,7 +158,7 @@ pub trait TestVector {
         test_mode: TestMode,
     ) -> TlsEnvGuard {
         initialize_tv_case_from_file(tv_file_path, file_format, test_mode)
-            .expect("Failed to initialize test vector")
+            .unwrap_or_else(|e| panic!("Failed to initialize test vector: {e}"))
     }
 
     #[inline(always)]
@@ -105,22 +183,13 @@ pub trait TestVector {
             pretty_location_file(caller_location.file()),
             caller_location.line()
         ));
-        let value = crate::process_next_entry(
+        let value = crate::process_next_value(
             crate::TestVectorEntryType::Const,
-            test_vec_field.description.clone(),
-            test_vec_field.name.clone(),
+            test_vec_field,
             &observed_value,
             caller_location,
-            Some(test_vec_field.test_value_field_code_location.clone()),
-            test_vec_field.serializer.as_ref().unwrap_or_else(|| {
-                panic!("Serializer was not provided for test field: {test_vec_field:?}")
-            }),
-            Some(test_vec_field.deserializer.as_ref().unwrap_or_else(|| {
-                panic!("Deserializer was not provided for test field: {test_vec_field:?}")
-            })),
-            test_vec_field.offload,
         )
-        .expect("Error processing observed test vector value")
+        .unwrap_or_else(|e| panic!("Error processing observed test vector value: {e}"))
         .expect("Unexpected error processing observed test vector const: no value was loaded");
         value
     }
@@ -135,22 +204,13 @@ pub trait TestVector {
             pretty_location_file(caller_location.file()),
             caller_location.line()
         ));
-        *observed_mut_value = crate::process_next_entry(
+        *observed_mut_value = crate::process_next_value(
             crate::TestVectorEntryType::Const,
-            test_vec_field.description.clone(),
-            test_vec_field.name.clone(),
+            test_vec_field,
             observed_mut_value,
             caller_location,
-            Some(test_vec_field.test_value_field_code_location.clone()),
-            test_vec_field.serializer.as_ref().unwrap_or_else(|| {
-                panic!("Serializer was not provided for test field: {test_vec_field:?}")
-            }),
-            Some(test_vec_field.deserializer.as_ref().unwrap_or_else(|| {
-                panic!("Deserializer was not provided for test field: {test_vec_field:?}")
-            })),
-            test_vec_field.offload,
         )
-        .expect("Error processing observed test vector value")
+        .unwrap_or_else(|e| panic!("Error processing observed test vector value: {e}"))
         .expect("Unexpected error processing observed test vector const: no value was loaded");
     }
 
@@ -164,20 +224,13 @@ pub trait TestVector {
             pretty_location_file(caller_location.file()),
             caller_location.line()
         ));
-        crate::process_next_entry(
+        crate::process_next_value(
             crate::TestVectorEntryType::Output,
-            test_vec_field.description.clone(),
-            test_vec_field.name.clone(),
+            test_vec_field,
             observed_value,
             caller_location,
-            Some(test_vec_field.test_value_field_code_location.clone()),
-            test_vec_field.serializer.as_ref().unwrap_or_else(|| {
-                panic!("Serializer was not provided for test field: {test_vec_field:?}")
-            }),
-            None,
-            test_vec_field.offload,
         )
-        .expect("Error checking observed test vector value");
+        .unwrap_or_else(|e| panic!("Error checking observed test vector value: {e}"));
     }
 
     /// Returns `true` when the implementation actively records/checks vectors.
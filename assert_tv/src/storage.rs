@@ -34,7 +34,7 @@ pub(crate) mod storage_global {
             TEST_VEC_ENV.0.get_or_init(|| Mutex::new(None))
         }
 
-        pub(crate) fn initialize_with(self) -> anyhow::Result<TlsEnvGuard> {
+        pub(crate) fn initialize_with(self) -> Result<TlsEnvGuard, crate::TestVectorError> {
             let mut test_vec_env_lock = crate::test_vec_impl::TestVecEnv::get_global()
                 .lock()
                 .expect("Global poisoned");
@@ -46,24 +46,23 @@ pub(crate) mod storage_global {
             })
         }
 
-        pub(crate) fn with_global<F, R>(f: F) -> anyhow::Result<R>
+        pub(crate) fn with_global<F, R>(f: F) -> Result<R, crate::TestVectorError>
         where
-            F: FnOnce(&mut crate::test_vec_impl::TestVecEnv) -> anyhow::Result<R>,
+            F: FnOnce(&mut crate::test_vec_impl::TestVecEnv) -> Result<R, crate::TestVectorError>,
         {
             let mut test_vec_env_lock = crate::test_vec_impl::TestVecEnv::get_global()
                 .lock()
                 .expect("Global poisoned");
             f(test_vec_env_lock
                 .as_mut()
-                .ok_or_else(|| anyhow::anyhow!("TestEnv not initialized."))?)
+                .ok_or(crate::TestVectorError::NotInitialized)?)
         }
     }
 }
 
 #[cfg(feature = "tls")]
 pub(crate) mod tls_storage {
-    use crate::TestVecEnv;
-    use anyhow::bail;
+    use crate::{TestVecEnv, TestVectorError};
     use std::cell::RefCell;
     use std::marker::PhantomData;
 
@@ -89,26 +88,27 @@ pub(crate) mod tls_storage {
     }
 
     impl TestVecEnv {
-        pub(crate) fn initialize_with(self) -> anyhow::Result<TlsEnvGuard> {
+        pub(crate) fn initialize_with(self) -> Result<TlsEnvGuard, TestVectorError> {
             let previous = TEST_VEC_ENV.replace(Some(self));
             if let Some(previous) = previous {
-                bail!("Initialized a new test vector while a previous test vector is already initialized: {:?}",
-                    previous.tv_file_path);
+                return Err(TestVectorError::AlreadyInitialized {
+                    active_file: previous.tv_file_path,
+                });
             }
             Ok(TlsEnvGuard {
                 _marker: PhantomData,
             })
         }
 
-        pub(crate) fn with_global<F, R>(f: F) -> anyhow::Result<R>
+        pub(crate) fn with_global<F, R>(f: F) -> Result<R, TestVectorError>
         where
-            F: FnOnce(&mut TestVecEnv) -> anyhow::Result<R>,
+            F: FnOnce(&mut TestVecEnv) -> Result<R, TestVectorError>,
         {
             TEST_VEC_ENV.with(|tv_env_cell| {
                 let mut tv_env_borrowed = tv_env_cell.borrow_mut();
                 let tv_env: &mut TestVecEnv = tv_env_borrowed
                     .as_mut()
-                    .ok_or_else(|| anyhow::anyhow!("TestEnv not initialized."))?;
+                    .ok_or(TestVectorError::NotInitialized)?;
                 f(tv_env)
             })
         }
@@ -0,0 +1,170 @@
+//! Streaming of JSON Lines test vectors that are too large to keep in memory.
+//!
+//! With `TestVectorOptions::streaming`, an `Init` session appends every recorded entry to
+//! `<file>.partial` while it is processed and moves it over the test vector file when the
+//! session is finalized, so a panicking test keeps the entries recorded up to the panic.
+//! A `Check` session reads the file one entry at a time as the entries are replayed.
+use crate::header::{HeaderLine, TestVectorHeader};
+use crate::test_vec_impl::TestVectorEntry;
+use crate::{TestVectorError, TestVectorFileFormat};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Lines, Write};
+use std::path::{Path, PathBuf};
+
+/// The file an `Init` session streams to before it is finalized.
+fn partial_path(tv_file_path: &Path) -> PathBuf {
+    let mut file_name = tv_file_path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".partial");
+    tv_file_path.with_file_name(file_name)
+}
+
+pub(crate) struct EntryWriter {
+    tv_file_path: PathBuf,
+    partial_path: PathBuf,
+    writer: BufWriter<File>,
+}
+
+impl EntryWriter {
+    /// Create `<file>.partial`, starting with the header line.
+    pub(crate) fn create(
+        tv_file_path: &Path,
+        header: &TestVectorHeader,
+    ) -> Result<Self, TestVectorError> {
+        let partial_path = partial_path(tv_file_path);
+        let io_error = |source| TestVectorError::Io {
+            path: partial_path.clone(),
+            source,
+        };
+        if let Some(parent) = partial_path.parent() {
+            std::fs::create_dir_all(parent).map_err(io_error)?;
+        }
+        let partial_file = File::create(&partial_path).map_err(io_error)?;
+        let mut writer = BufWriter::new(partial_file);
+        let header_line = HeaderLine {
+            header: header.clone(),
+        };
+        serde_json::to_writer(&mut writer, &header_line).map_err(|e| TestVectorError::Encode {
+            path: partial_path.clone(),
+            format: TestVectorFileFormat::JsonLines,
+            message: e.to_string(),
+        })?;
+        writer.write_all(b"\n").map_err(io_error)?;
+        Ok(Self {
+            tv_file_path: tv_file_path.to_path_buf(),
+            partial_path,
+            writer,
+        })
+    }
+
+    /// Append `entry` as a line, writing its offloaded or compressed value first.
+    pub(crate) fn append(&mut self, mut entry: TestVectorEntry) -> Result<(), TestVectorError> {
+        entry.save_offloaded_value(&self.tv_file_path)?;
+        entry.save_compressed_value()?;
+        serde_json::to_writer(&mut self.writer, &entry).map_err(|e| TestVectorError::Encode {
+            path: self.partial_path.clone(),
+            format: TestVectorFileFormat::JsonLines,
+            message: e.to_string(),
+        })?;
+        // flushed per entry, so the recording survives an aborted test process
+        self.writer
+            .write_all(b"\n")
+            .and_then(|()| self.writer.flush())
+            .map_err(|source| TestVectorError::Io {
+                path: self.partial_path.clone(),
+                source,
+            })
+    }
+
+    /// Flush the appended entries and move them over the test vector file.
+    pub(crate) fn finish(mut self) -> Result<(), TestVectorError> {
+        self.writer.flush().map_err(|source| TestVectorError::Io {
+            path: self.partial_path.clone(),
+            source,
+        })?;
+        std::fs::rename(&self.partial_path, &self.tv_file_path).map_err(|source| {
+            TestVectorError::Io {
+                path: self.tv_file_path.clone(),
+                source,
+            }
+        })
+    }
+}
+
+pub(crate) struct EntryReader {
+    tv_file_path: PathBuf,
+    lines: Lines<BufReader<File>>,
+    /// Number of lines read so far, for error messages.
+    line_number: usize,
+    /// Number of entries read so far.
+    entries_read: usize,
+    /// Whether the first line was checked for a header.
+    header_checked: bool,
+}
+
+impl EntryReader {
+    pub(crate) fn open(tv_file_path: &Path) -> Result<Self, TestVectorError> {
+        let tv_file = File::open(tv_file_path).map_err(|source| TestVectorError::Io {
+            path: tv_file_path.to_path_buf(),
+            source,
+        })?;
+        Ok(Self {
+            tv_file_path: tv_file_path.to_path_buf(),
+            lines: BufReader::new(tv_file).lines(),
+            line_number: 0,
+            entries_read: 0,
+            header_checked: false,
+        })
+    }
+
+    /// The next entry, without resolving its offloaded or compressed value.
+    fn next_raw(&mut self) -> Result<Option<TestVectorEntry>, TestVectorError> {
+        for line in self.lines.by_ref() {
+            let line = line.map_err(|source| TestVectorError::Io {
+                path: self.tv_file_path.clone(),
+                source,
+            })?;
+            self.line_number += 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+            // the header, if any, is the first line
+            if !self.header_checked {
+                self.header_checked = true;
+                if HeaderLine::parse(&line, self.line_number, &self.tv_file_path)?.is_some() {
+                    continue;
+                }
+            } else {
+                HeaderLine::reject_misplaced(&line, self.line_number, &self.tv_file_path)?;
+            }
+            let entry = serde_json::from_str(&line).map_err(|e| TestVectorError::Parse {
+                path: self.tv_file_path.clone(),
+                format: TestVectorFileFormat::JsonLines,
+                message: format!("line {}: {}", self.line_number, e),
+            })?;
+            self.entries_read += 1;
+            return Ok(Some(entry));
+        }
+        Ok(None)
+    }
+
+    /// The next entry with its offloaded or compressed value resolved, `None` at the end
+    /// of the file.
+    pub(crate) fn next_entry(&mut self) -> Result<Option<TestVectorEntry>, TestVectorError> {
+        let Some(mut entry) = self.next_raw()? else {
+            return Ok(None);
+        };
+        let entry_index = self.entries_read - 1;
+        entry.load_offloaded_value(&self.tv_file_path, entry_index)?;
+        entry.load_compressed_value(&self.tv_file_path, entry_index)?;
+        Ok(Some(entry))
+    }
+
+    /// Index and entry of every entry that was not read yet; values are not resolved.
+    pub(crate) fn remaining(mut self) -> Result<Vec<(usize, TestVectorEntry)>, TestVectorError> {
+        let mut remaining = Vec::new();
+        while let Some(entry) = self.next_raw()? {
+            remaining.push((self.entries_read - 1, entry));
+        }
+        Ok(remaining)
+    }
+}
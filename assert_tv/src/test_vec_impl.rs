@@ -1,9 +1,25 @@
-use crate::{DynDeserializer, DynSerializer, TestMode, TestVectorFileFormat, TlsEnvGuard};
+use crate::diff::{
+    diff_values, mask_paths, sort_unordered_arrays, CompareOptions, DEFAULT_REDACTION,
+};
+use crate::error::{OffloadDigest, UnconsumedEntry};
+use crate::header::{HeaderLine, TestVectorHeader};
+use crate::json_path::JsonPath;
+use crate::offload::{
+    blob_file_name, blob_path, byte_array, compress, content_hash, decompress, legacy_sidecar_path,
+    remove_legacy_sidecars, remove_stale_blobs, write_blob, OffloadCodec, OffloadEncoding,
+};
+use crate::stream::{EntryReader, EntryWriter};
+use crate::{
+    DynDeserializer, DynSerializer, FailureMode, MatchingStrategy, TestMode, TestValue,
+    TestVectorError, TestVectorFileFormat, TestVectorOptions, TlsEnvGuard, ValueDifference,
+};
 use anyhow::{anyhow, bail, Context};
+use base64::prelude::{Engine, BASE64_STANDARD};
 use log::warn;
 use serde::{Deserialize, Serialize};
-use std::io::{Cursor, Read, Write};
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
 
 #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
 pub struct TestVectorEntry {
@@ -17,6 +33,24 @@ pub struct TestVectorEntry {
     #[serde(default)]
     #[serde(skip_serializing_if = "is_false")]
     offload: bool,
+    /// Content hash naming the blob of an offloaded value.
+    ///
+    /// Missing for files written with index-named sidecar files.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    offload_hash: Option<String>,
+    /// Length in bytes of the uncompressed serialized offloaded value.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    offload_len: Option<u64>,
+    /// Codec of an offloaded value; files without it used zstd.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    offload_codec: Option<OffloadCodec>,
+    /// Encoding of an offloaded value; files without it stored JSON.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    offload_encoding: Option<OffloadEncoding>,
+    /// The value is stored inline as a base64 encoded, zstd compressed JSON string.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "is_false")]
+    compress: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug, Copy, Eq, PartialEq, Clone)]
@@ -30,6 +64,9 @@ pub enum TestVectorEntryType {
 
 #[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
 pub struct TestVectorData {
+    /// Provenance of the file; files written before headers were introduced have none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) header: Option<TestVectorHeader>,
     pub entries: Vec<TestVectorEntry>,
 }
 
@@ -43,124 +80,382 @@ pub struct TestVecEnv {
     loaded_tv_data: TestVectorData,
     recorded_tv_data: TestVectorData,
     test_mode: TestMode,
+    options: TestVectorOptions,
+    /// Marks which loaded entries were already paired with an observed entry.
+    consumed: Vec<bool>,
+    /// Marks which loaded entries have their offloaded or compressed value decoded.
+    resolved: Vec<bool>,
+    /// Loaded entry indices per key, used by `MatchingStrategy::Keyed`.
+    keyed_index: HashMap<EntryKey, Vec<usize>>,
+    /// Number of observed entries per key, used by `MatchingStrategy::Keyed`.
+    key_occurrences: HashMap<EntryKey, usize>,
+    /// Loaded indices of `Const` entries, used for positional matching in `Update` mode.
+    const_positions: Vec<usize>,
+    /// Number of constants replayed so far in `Update` mode.
+    replayed_consts: usize,
+    /// Output mismatches collected with `FailureMode::Deferred`.
+    deferred_failures: Vec<TestVectorError>,
+    /// Number of entries observed so far.
+    observed_entries: usize,
+    /// Appends recorded entries to disk in a streaming `Init` session.
+    entry_writer: Option<EntryWriter>,
+    /// Reads loaded entries on demand in a streaming `Check` session.
+    entry_reader: Option<EntryReader>,
 }
 
-impl TestVectorData {
-    fn load_from_file<T: Into<PathBuf>>(
-        tv_file_path: T,
-        file_format: TestVectorFileFormat,
-    ) -> anyhow::Result<Self> {
-        let tv_file_path = tv_file_path.into();
+/// `(test_vec_set_code_location, name)` of an entry.
+type EntryKey = (Option<String>, Option<String>);
+
+impl TestVectorEntry {
+    fn key(&self) -> EntryKey {
+        (self.test_vec_set_code_location.clone(), self.name.clone())
+    }
+
+    /// Replace the stored reference of an offloaded entry with the value read from its blob.
+    pub(crate) fn load_offloaded_value(
+        &mut self,
+        tv_file_path: &Path,
+        entry_index: usize,
+    ) -> Result<(), TestVectorError> {
+        if !self.offload {
+            return Ok(());
+        }
+        if !self.value.is_null() {
+            warn!("Test value entry is set to offload but still has a value already loaded")
+        }
+        let codec = self.offload_codec.unwrap_or_default();
+        let encoding = self.offload_encoding.unwrap_or_default();
+        let offloaded_path = match &self.offload_hash {
+            Some(hash) => blob_path(tv_file_path, hash, codec, encoding),
+            None => legacy_sidecar_path(tv_file_path, entry_index),
+        };
+        let offloaded_value_bytes =
+            std::fs::read(&offloaded_path).map_err(|source| TestVectorError::OffloadMissing {
+                entry_index,
+                path: offloaded_path.clone(),
+                source,
+            })?;
+        let corrupted = |message: String| TestVectorError::OffloadCorrupted {
+            entry_index,
+            path: offloaded_path.clone(),
+            message,
+        };
+        let offloaded_value_bytes = codec
+            .decode(offloaded_value_bytes)
+            .map_err(|e| corrupted(e.to_string()))?;
+        if let Some(expected_hash) = &self.offload_hash {
+            let actual_hash = content_hash(&offloaded_value_bytes);
+            let actual_len = offloaded_value_bytes.len() as u64;
+            if *expected_hash != actual_hash
+                || self.offload_len.is_some_and(|len| len != actual_len)
+            {
+                return Err(TestVectorError::OffloadIntegrity {
+                    entry_index,
+                    name: self.name.clone(),
+                    path: offloaded_path,
+                    expected: Box::new(OffloadDigest {
+                        hash: expected_hash.clone(),
+                        len: self.offload_len,
+                    }),
+                    actual: Box::new(OffloadDigest {
+                        hash: actual_hash,
+                        len: Some(actual_len),
+                    }),
+                });
+            }
+        }
+        self.value = encoding.decode(&offloaded_value_bytes).map_err(corrupted)?;
+        // the blob name is derived from the value again when storing
+        self.offload_hash = None;
+        self.offload_len = None;
+        Ok(())
+    }
 
-        let mut tv_file = std::fs::File::open(tv_file_path.clone()).map_err(|e| {
-            anyhow::anyhow!(
-                "Failed to open test vector file ({:?}): {}",
-                tv_file_path,
+    pub(crate) fn load_compressed_value(
+        &mut self,
+        tv_file_path: &Path,
+        entry_index: usize,
+    ) -> Result<(), TestVectorError> {
+        // offloaded values are compressed in their sidecar file already
+        if !self.compress || self.offload {
+            return Ok(());
+        }
+        let corrupted = |message: String| TestVectorError::CompressedCorrupted {
+            entry_index,
+            path: tv_file_path.to_path_buf(),
+            message,
+        };
+        let encoded = self
+            .value
+            .as_str()
+            .ok_or_else(|| corrupted("expected a base64 string".to_string()))?;
+        let compressed = BASE64_STANDARD
+            .decode(encoded)
+            .map_err(|e| corrupted(e.to_string()))?;
+        let serialized = decompress(compressed).map_err(|e| corrupted(e.to_string()))?;
+        self.value = serde_json::from_slice(&serialized).map_err(|e| {
+            corrupted(format!(
+                "Failed to parse compressed value as a json value: {}",
                 e
-            )
+            ))
         })?;
-        let mut tv_data: TestVectorData = match file_format {
-            TestVectorFileFormat::Json => serde_json::from_reader(tv_file).map_err(|e| {
-                anyhow::anyhow!(
-                    "Failed to parse test vector file ({:?}) as json: {}",
-                    tv_file_path,
-                    e
-                )
-            })?,
-            TestVectorFileFormat::Yaml => serde_yaml::from_reader(tv_file).map_err(|e| {
-                anyhow::anyhow!(
-                    "Failed to parse test vector file ({:?}) as yaml: {}",
-                    tv_file_path,
-                    e
-                )
-            })?,
-            TestVectorFileFormat::Toml => {
-                let mut buffer: String = String::new();
-                tv_file
-                    .read_to_string(&mut buffer)
-                    .map_err(|e| anyhow::anyhow!("Failed to read test vector file: {}", e))?;
-                toml::from_str(buffer.as_ref()).map_err(|e| {
-                    anyhow::anyhow!(
-                        "Failed to parse test vector file ({:?}) as toml: {}",
-                        tv_file_path,
-                        e
-                    )
-                })?
+        Ok(())
+    }
+
+    pub(crate) fn save_compressed_value(&mut self) -> Result<(), TestVectorError> {
+        if !self.compress || self.offload {
+            return Ok(());
+        }
+        let serialized =
+            serde_json::to_vec(&self.value).map_err(|e| TestVectorError::Serialize {
+                name: self.name.clone(),
+                source: e.into(),
+            })?;
+        let compressed = compress(serialized).map_err(|e| TestVectorError::Serialize {
+            name: self.name.clone(),
+            source: e.into(),
+        })?;
+        self.value = serde_json::Value::String(BASE64_STANDARD.encode(compressed));
+        Ok(())
+    }
+
+    /// Write the value of an offloaded entry to its blob and keep only the reference.
+    pub(crate) fn save_offloaded_value(
+        &mut self,
+        tv_file_path: &Path,
+    ) -> Result<(), TestVectorError> {
+        if !self.offload {
+            return Ok(());
+        }
+        let encoding = self.offload_encoding.unwrap_or_default();
+        let serialized =
+            encoding
+                .encode(&self.value)
+                .map_err(|message| TestVectorError::Serialize {
+                    name: self.name.clone(),
+                    source: anyhow!(message),
+                })?;
+        let hash = content_hash(&serialized);
+        let len = serialized.len() as u64;
+        let codec = self.offload_codec.unwrap_or_default();
+        let offloaded_path = blob_path(tv_file_path, &hash, codec, encoding);
+        write_blob(&offloaded_path, serialized, &hash, codec).map_err(|source| {
+            TestVectorError::Io {
+                path: offloaded_path.clone(),
+                source,
             }
-        };
-        tv_data.load_offloaded_values(tv_file_path.clone())?;
-        Ok(tv_data)
+        })?;
+
+        self.value = serde_json::Value::Null;
+        self.offload_hash = Some(hash);
+        self.offload_len = Some(len);
+        Ok(())
     }
+}
 
-    fn load_offloaded_values(&mut self, tv_file_path: PathBuf) -> anyhow::Result<()> {
-        for (entry_index, entry) in self.entries.iter_mut().enumerate() {
-            if !(entry.offload) {
-                continue;
+impl TestVecEnv {
+    /// Find the loaded entry paired with the next observed entry and mark it as consumed.
+    fn next_loaded_entry(
+        &mut self,
+        observed_entry: &TestVectorEntry,
+    ) -> Result<Option<TestVectorEntry>, TestVectorError> {
+        if let Some(entry_reader) = &mut self.entry_reader {
+            // streamed entries are paired positionally and consumed as they are read
+            return entry_reader.next_entry();
+        }
+        let entry_index = match (self.test_mode, self.options.matching) {
+            // update mode re-records outputs, only constants are replayed
+            (TestMode::Update, _) if observed_entry.entry_type == TestVectorEntryType::Output => {
+                return Ok(None)
             }
-            if !entry.value.is_null() {
-                warn!("Test value entry is set to offload but still has a value already loaded")
+            // constants are paired by their position among constants, so added or removed
+            // outputs do not shift them
+            (TestMode::Update, MatchingStrategy::Positional) => {
+                let nth = self.replayed_consts;
+                self.replayed_consts += 1;
+                match self.const_positions.get(nth) {
+                    Some(entry_index) => *entry_index,
+                    None => return Ok(None),
+                }
             }
-            let offloaded_path = append_suffix_to_filename(
-                &tv_file_path,
-                format!("_offloaded_value_{}.zstd", entry_index).as_str(),
-            );
-            let mut offloaded_value_file =
-                std::fs::File::open(offloaded_path.clone()).map_err(|e| {
-                    anyhow::anyhow!(
-                        "Failed to open offloaded value file ({:?}): {}",
-                        offloaded_path,
-                        e
-                    )
-                })?;
-            let mut offloaded_value_bytes = Vec::new();
-            offloaded_value_file
-                .read_to_end(&mut offloaded_value_bytes)
-                .map_err(|e| anyhow::anyhow!("Failed to read offloaded value file: {}", e))?;
-            drop(offloaded_value_file);
-            let offloaded_value_bytes = decompress(offloaded_value_bytes)?;
-            let offloaded_value: serde_json::value::Value =
-                serde_json::from_slice(&offloaded_value_bytes).map_err(|e| {
-                    anyhow::anyhow!("Failed to parse offloaded value as a json value: {}", e)
-                })?;
-            entry.value = offloaded_value;
+            (_, MatchingStrategy::Positional) => self.observed_entries,
+            (_, MatchingStrategy::Keyed) => {
+                let key = observed_entry.key();
+                let occurrence = self.key_occurrences.entry(key.clone()).or_insert(0);
+                let nth = *occurrence;
+                *occurrence += 1;
+                match self
+                    .keyed_index
+                    .get(&key)
+                    .and_then(|indices| indices.get(nth))
+                {
+                    Some(entry_index) => *entry_index,
+                    None => return Ok(None),
+                }
+            }
+        };
+        if entry_index >= self.loaded_tv_data.entries.len() {
+            return Ok(None);
         }
-        Ok(())
+        self.resolve_loaded_entry(entry_index)?;
+        self.consumed[entry_index] = true;
+        Ok(Some(self.loaded_tv_data.entries[entry_index].clone()))
     }
 
-    fn save_offloaded_values(&mut self, tv_file_path: PathBuf) -> anyhow::Result<()> {
-        for (entry_index, entry) in self.entries.iter_mut().enumerate() {
-            if !entry.offload {
-                continue;
-            }
+    /// Decode the offloaded or compressed value of a loaded entry, once it is reached.
+    ///
+    /// The whole file, including inline values, is parsed when the session starts; only
+    /// reading offloaded blobs and decompressing values is deferred, so a session only
+    /// decodes the values of the entries it replays.
+    fn resolve_loaded_entry(&mut self, entry_index: usize) -> Result<(), TestVectorError> {
+        if self.resolved[entry_index] {
+            return Ok(());
+        }
+        let entry = &mut self.loaded_tv_data.entries[entry_index];
+        entry.load_offloaded_value(&self.tv_file_path, entry_index)?;
+        entry.load_compressed_value(&self.tv_file_path, entry_index)?;
+        self.resolved[entry_index] = true;
+        Ok(())
+    }
 
-            let offloaded_path = append_suffix_to_filename(
-                &tv_file_path,
-                format!("_offloaded_value_{}.zstd", entry_index).as_str(),
-            );
+    /// Blobs referenced by the test vector file before it is rewritten; none if it does
+    /// not exist or cannot be parsed.
+    fn stored_blobs(&self) -> HashSet<String> {
+        if !self.tv_file_path.is_file() {
+            return HashSet::new();
+        }
+        TestVectorData::parse_file(&self.tv_file_path, self.file_format)
+            .map(|tv_data| tv_data.referenced_blobs())
+            .unwrap_or_default()
+    }
+}
 
-            let serialized = serde_json::to_vec(&entry.value).map_err(|e| {
-                anyhow::anyhow!("Failed to serialize value at index {}: {}", entry_index, e)
-            })?;
+impl TestVectorData {
+    /// Parse a test vector file without resolving offloaded or compressed values.
+    ///
+    /// Sessions resolve them per entry with `TestVecEnv::resolve_loaded_entry` when the
+    /// entry is reached.
+    pub(crate) fn parse_file(
+        tv_file_path: &Path,
+        file_format: TestVectorFileFormat,
+    ) -> Result<Self, TestVectorError> {
+        let tv_file_path = tv_file_path.to_path_buf();
+        let file_format = file_format.resolve(&tv_file_path)?;
+        let tv_file = std::fs::File::open(&tv_file_path).map_err(|source| TestVectorError::Io {
+            path: tv_file_path.clone(),
+            source,
+        })?;
+        let read_to_string = |mut tv_file: std::fs::File| {
+            let mut buffer = String::new();
+            tv_file
+                .read_to_string(&mut buffer)
+                .map_err(|source| TestVectorError::Io {
+                    path: tv_file_path.clone(),
+                    source,
+                })?;
+            Ok::<_, TestVectorError>(buffer)
+        };
+        let parse_error = |message: String| TestVectorError::Parse {
+            path: tv_file_path.clone(),
+            format: file_format,
+            message,
+        };
+        let tv_data: TestVectorData = match file_format {
+            TestVectorFileFormat::Json => {
+                serde_json::from_reader(tv_file).map_err(|e| parse_error(e.to_string()))?
+            }
+            TestVectorFileFormat::Yaml => {
+                serde_yaml::from_reader(tv_file).map_err(|e| parse_error(e.to_string()))?
+            }
+            TestVectorFileFormat::Toml => {
+                let buffer = read_to_string(tv_file)?;
+                toml::from_str(buffer.as_ref()).map_err(|e| parse_error(e.to_string()))?
+            }
+            #[cfg(feature = "ron")]
+            TestVectorFileFormat::Ron => {
+                let buffer = read_to_string(tv_file)?;
+                ron::from_str(&buffer).map_err(|e| parse_error(e.to_string()))?
+            }
+            TestVectorFileFormat::JsonLines => {
+                let buffer = read_to_string(tv_file)?;
+                let mut lines = buffer
+                    .lines()
+                    .enumerate()
+                    .filter(|(_, line)| !line.trim().is_empty())
+                    .peekable();
+                // the header, if any, is the first line
+                let header = match lines.peek() {
+                    Some((line_index, line)) => {
+                        HeaderLine::parse(line, line_index + 1, &tv_file_path)?
+                    }
+                    None => None,
+                };
+                if header.is_some() {
+                    lines.next();
+                }
+                let entries = lines
+                    .map(|(line_index, line)| {
+                        HeaderLine::reject_misplaced(line, line_index + 1, &tv_file_path)?;
+                        serde_json::from_str(line)
+                            .map_err(|e| parse_error(format!("line {}: {}", line_index + 1, e)))
+                    })
+                    .collect::<Result<_, _>>()?;
+                TestVectorData { header, entries }
+            }
+            #[cfg(feature = "cbor")]
+            TestVectorFileFormat::Cbor => {
+                ciborium::de::from_reader(std::io::BufReader::new(tv_file))
+                    .map_err(|e| parse_error(e.to_string()))?
+            }
+            #[cfg(feature = "msgpack")]
+            TestVectorFileFormat::MessagePack => {
+                rmp_serde::from_read(std::io::BufReader::new(tv_file))
+                    .map_err(|e| parse_error(e.to_string()))?
+            }
+            #[allow(unreachable_patterns)]
+            format => return Err(parse_error(feature_disabled(format))),
+        };
+        if let Some(header) = &tv_data.header {
+            header.validate(&tv_file_path, file_format)?;
+        }
+        Ok(tv_data)
+    }
 
-            let compressed = compress(serialized)?;
+    /// Index and blob file name (`None` for index-named sidecars) of every offloaded entry.
+    pub(crate) fn offload_references(&self) -> Vec<(usize, Option<String>)> {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.offload)
+            .map(|(entry_index, entry)| {
+                let codec = entry.offload_codec.unwrap_or_default();
+                let encoding = entry.offload_encoding.unwrap_or_default();
+                let blob_file_name = entry
+                    .offload_hash
+                    .as_ref()
+                    .map(|hash| blob_file_name(hash, codec, encoding));
+                (entry_index, blob_file_name)
+            })
+            .collect()
+    }
 
-            let mut file = std::fs::File::create(&offloaded_path).map_err(|e| {
-                anyhow::anyhow!(
-                    "Failed to create or overwrite offloaded value file at {:?}: {}",
-                    offloaded_path,
-                    e
-                )
-            })?;
+    fn referenced_blobs(&self) -> HashSet<String> {
+        self.offload_references()
+            .into_iter()
+            .filter_map(|(_, blob_file_name)| blob_file_name)
+            .collect()
+    }
 
-            file.write_all(&compressed).map_err(|e| {
-                anyhow::anyhow!(
-                    "Failed to write to offloaded value file at {:?}: {}",
-                    offloaded_path,
-                    e
-                )
-            })?;
+    fn save_compressed_values(&mut self) -> Result<(), TestVectorError> {
+        for entry in self.entries.iter_mut() {
+            entry.save_compressed_value()?;
+        }
+        Ok(())
+    }
 
-            entry.value = serde_json::Value::Null;
+    fn save_offloaded_values(&mut self, tv_file_path: PathBuf) -> Result<(), TestVectorError> {
+        for entry in self.entries.iter_mut() {
+            entry.save_offloaded_value(&tv_file_path)?;
         }
         Ok(())
     }
@@ -169,43 +464,118 @@ impl TestVectorData {
         &mut self,
         tv_file_path: T,
         file_format: TestVectorFileFormat,
-    ) -> anyhow::Result<()> {
+    ) -> Result<(), TestVectorError> {
         let tv_file_path = tv_file_path.into();
+        self.header
+            .get_or_insert_with(|| TestVectorHeader::current(&TestVectorOptions::default()));
         self.save_offloaded_values(tv_file_path.clone())?;
+        self.save_compressed_values()?;
+        let io_error = |source| TestVectorError::Io {
+            path: tv_file_path.clone(),
+            source,
+        };
         if let Some(parent) = tv_file_path.parent() {
-            std::fs::create_dir_all(parent).map_err(|e| {
-                anyhow::anyhow!(
-                    "Failed to create parent directories for test vector file ({:?}): {}",
-                    tv_file_path,
-                    e
-                )
-            })?;
+            std::fs::create_dir_all(parent).map_err(io_error)?;
         }
-        let mut tv_file = std::fs::File::create(tv_file_path)
-            .map_err(|e| anyhow::anyhow!("Failed to create test vector file: {}", e))?;
+        let encode_error = |message: String| TestVectorError::Encode {
+            path: tv_file_path.clone(),
+            format: file_format,
+            message,
+        };
+        let mut tv_file = std::fs::File::create(&tv_file_path).map_err(io_error)?;
         match file_format {
             TestVectorFileFormat::Json => serde_json::to_writer_pretty(tv_file, &self)
-                .map_err(|e| anyhow::anyhow!("Failed to write test vector file as json: {}", e))?,
-            TestVectorFileFormat::Yaml => serde_yaml::to_writer(tv_file, &self)
-                .map_err(|e| anyhow::anyhow!("Failed to write test vector file as yaml: {}", e))?,
+                .map_err(|e| encode_error(e.to_string()))?,
+            TestVectorFileFormat::Yaml => {
+                serde_yaml::to_writer(tv_file, &self).map_err(|e| encode_error(e.to_string()))?
+            }
             TestVectorFileFormat::Toml => {
-                let tv_serialized: String = toml::to_string(&self).map_err(|e| {
-                    anyhow::anyhow!("Failed to serialize test vector as toml: {}", e)
-                })?;
+                let tv_serialized: String =
+                    toml::to_string(&self).map_err(|e| encode_error(e.to_string()))?;
                 tv_file
-                    .write(tv_serialized.as_bytes())
-                    .map_err(|e| anyhow::anyhow!("Failed to write test vector: {}", e))?;
+                    .write_all(tv_serialized.as_bytes())
+                    .map_err(io_error)?;
             }
+            #[cfg(feature = "ron")]
+            TestVectorFileFormat::Ron => {
+                let tv_serialized =
+                    ron::ser::to_string_pretty(&self, ron::ser::PrettyConfig::default())
+                        .map_err(|e| encode_error(e.to_string()))?;
+                tv_file
+                    .write_all(tv_serialized.as_bytes())
+                    .map_err(io_error)?;
+            }
+            TestVectorFileFormat::JsonLines => {
+                let mut writer = std::io::BufWriter::new(tv_file);
+                if let Some(header) = &self.header {
+                    let header_line = HeaderLine {
+                        header: header.clone(),
+                    };
+                    serde_json::to_writer(&mut writer, &header_line)
+                        .map_err(|e| encode_error(e.to_string()))?;
+                    writer.write_all(b"\n").map_err(io_error)?;
+                }
+                for entry in &self.entries {
+                    serde_json::to_writer(&mut writer, entry)
+                        .map_err(|e| encode_error(e.to_string()))?;
+                    writer.write_all(b"\n").map_err(io_error)?;
+                }
+                writer.flush().map_err(io_error)?;
+            }
+            #[cfg(feature = "cbor")]
+            TestVectorFileFormat::Cbor => {
+                let mut writer = std::io::BufWriter::new(tv_file);
+                ciborium::ser::into_writer(&self, &mut writer)
+                    .map_err(|e| encode_error(e.to_string()))?;
+                writer.flush().map_err(io_error)?;
+            }
+            #[cfg(feature = "msgpack")]
+            TestVectorFileFormat::MessagePack => {
+                let tv_serialized =
+                    rmp_serde::to_vec_named(&self).map_err(|e| encode_error(e.to_string()))?;
+                tv_file.write_all(&tv_serialized).map_err(io_error)?;
+            }
+            #[allow(unreachable_patterns)]
+            format => return Err(encode_error(feature_disabled(format))),
         };
         Ok(())
     }
 }
 
+/// Whether the cargo feature a format needs is enabled.
+fn format_enabled(format: TestVectorFileFormat) -> bool {
+    #[allow(clippy::match_like_matches_macro)]
+    match format {
+        TestVectorFileFormat::Ron => cfg!(feature = "ron"),
+        TestVectorFileFormat::Cbor => cfg!(feature = "cbor"),
+        TestVectorFileFormat::MessagePack => cfg!(feature = "msgpack"),
+        _ => true,
+    }
+}
+
+/// Error message for a format whose cargo feature is not enabled.
+fn feature_disabled(format: TestVectorFileFormat) -> String {
+    let feature = match format {
+        TestVectorFileFormat::Ron => "ron",
+        TestVectorFileFormat::Cbor => "cbor",
+        TestVectorFileFormat::MessagePack => "msgpack",
+        _ => unreachable!("{:?} is always available or resolved", format),
+    };
+    format!("the `{}` feature of assert_tv is not enabled", feature)
+}
+
 /// Create a test‑vector session from the given file and mode.
 ///
 /// - In `Init`, starts with an empty in‑memory vector and writes it on finalize
 ///   if missing or changed.
 /// - In `Check`, loads and uses the existing file for validation.
+/// - In `Update`, loads the existing file to replay its constants and rewrites it on
+///   finalize with the newly observed outputs.
+/// - In `InitIfMissing`, uses `Check` if the file exists and `Init` otherwise.
+///
+/// Fails with `TestVectorError::FileCreationForbidden` if the file is missing, the session
+/// would create it and `TEST_VECTORS_CI` is set. `TestVectorFileFormat::Auto` is resolved
+/// from the file extension and fails with `TestVectorError::UnknownFormat` for unknown ones.
 ///
 /// Returns a guard that must be kept alive for the duration of the session; dropping it
 /// clears the global/thread‑local environment.
@@ -213,50 +583,230 @@ pub fn initialize_tv_case_from_file<T: Into<PathBuf>>(
     tv_file_path: T,
     file_format: TestVectorFileFormat,
     test_mode: TestMode,
-) -> anyhow::Result<TlsEnvGuard> {
+) -> Result<TlsEnvGuard, TestVectorError> {
+    initialize_tv_case_with_options(
+        tv_file_path,
+        file_format,
+        test_mode,
+        TestVectorOptions::default(),
+    )
+}
+
+/// Like `initialize_tv_case_from_file`, but with explicit session options.
+pub fn initialize_tv_case_with_options<T: Into<PathBuf>>(
+    tv_file_path: T,
+    file_format: TestVectorFileFormat,
+    test_mode: TestMode,
+    options: TestVectorOptions,
+) -> Result<TlsEnvGuard, TestVectorError> {
     let tv_file_path: PathBuf = tv_file_path.into();
+    let file_format = file_format.resolve(&tv_file_path)?;
+    let test_mode = match test_mode {
+        TestMode::InitIfMissing if tv_file_path.is_file() => TestMode::Check,
+        TestMode::InitIfMissing => TestMode::Init,
+        test_mode => test_mode,
+    };
+    if !format_enabled(file_format) {
+        // fail before the code under test runs instead of when the file is read or written
+        let (path, format, message) = (tv_file_path, file_format, feature_disabled(file_format));
+        return Err(match test_mode {
+            TestMode::Init => TestVectorError::Encode {
+                path,
+                format,
+                message,
+            },
+            _ => TestVectorError::Parse {
+                path,
+                format,
+                message,
+            },
+        });
+    }
+    if test_mode == TestMode::Init && !tv_file_path.is_file() && TestMode::file_creation_forbidden()
+    {
+        return Err(TestVectorError::FileCreationForbidden { path: tv_file_path });
+    }
+    let (mut entry_writer, mut entry_reader) = (None, None);
+    if options.streaming {
+        let unsupported = |reason: &str| TestVectorError::StreamingUnsupported {
+            path: tv_file_path.clone(),
+            reason: reason.to_string(),
+        };
+        if file_format != TestVectorFileFormat::JsonLines {
+            return Err(unsupported("only JSON Lines files can be streamed"));
+        }
+        if options.matching != MatchingStrategy::Positional {
+            return Err(unsupported(
+                "streamed entries can only be matched positionally",
+            ));
+        }
+        match test_mode {
+            TestMode::Init => {
+                let header = TestVectorHeader::current(&options);
+                entry_writer = Some(EntryWriter::create(&tv_file_path, &header)?)
+            }
+            TestMode::Check => entry_reader = Some(EntryReader::open(&tv_file_path)?),
+            _ => return Err(unsupported("only init and check mode can stream")),
+        }
+    }
     let loaded_tv_data = match test_mode {
+        TestMode::Check if options.streaming => TestVectorData {
+            header: None,
+            entries: Vec::new(),
+        },
         TestMode::Init => TestVectorData {
+            header: None,
             entries: Vec::new(),
         },
-        TestMode::Check => {
-            TestVectorData::load_from_file(&tv_file_path, file_format).map_err(|e| {
-                anyhow!("Error loading test vector. You may need to switch to init mode. Internal error: {}", e)
-            })?
+        TestMode::Check | TestMode::Update => {
+            TestVectorData::parse_file(&tv_file_path, file_format)?
         }
+        TestMode::InitIfMissing => unreachable!("resolved to Init or Check above"),
     };
+    let mut keyed_index: HashMap<EntryKey, Vec<usize>> = HashMap::new();
+    if options.matching == MatchingStrategy::Keyed {
+        for (entry_index, entry) in loaded_tv_data.entries.iter().enumerate() {
+            keyed_index
+                .entry(entry.key())
+                .or_default()
+                .push(entry_index);
+        }
+    }
+    let const_positions = loaded_tv_data
+        .entries
+        .iter()
+        .enumerate()
+        .filter(|(_, entry)| entry.entry_type == TestVectorEntryType::Const)
+        .map(|(entry_index, _)| entry_index)
+        .collect();
     let tv_env = TestVecEnv {
         tv_file_path,
+        const_positions,
+        replayed_consts: 0,
+        consumed: vec![false; loaded_tv_data.entries.len()],
+        resolved: vec![false; loaded_tv_data.entries.len()],
+        keyed_index,
+        key_occurrences: HashMap::new(),
+        deferred_failures: Vec::new(),
+        observed_entries: 0,
+        entry_writer,
+        entry_reader,
         loaded_tv_data,
         recorded_tv_data: TestVectorData {
+            header: Some(TestVectorHeader::current(&options)),
             entries: Vec::new(),
         },
         file_format,
         test_mode,
+        options,
     };
     TestVecEnv::initialize_with(tv_env)
 }
 
 /// Finalize the current test‑vector session.
 ///
-/// In `Init` mode, writes the recorded entries to disk (overwriting the file)
-/// when content changed or the file does not exist. In `Check` mode, this is a no‑op.
-pub fn finalize_tv_case() -> anyhow::Result<()> {
+/// In `Init` and `Update` mode, writes the recorded entries to disk (overwriting the file)
+/// when content changed or the file does not exist, and removes the blobs the previous file
+/// referenced unless another test vector file of the directory does. In `Check` mode, fails with
+/// `TestVectorError::UnconsumedEntries` if loaded entries were never replayed, unless
+/// `TestVectorOptions::allow_unconsumed` is set, and with the mismatches collected under
+/// `FailureMode::Deferred`.
+pub fn finalize_tv_case() -> Result<(), TestVectorError> {
     TestVecEnv::with_global(|tv_env| {
         match tv_env.test_mode {
             TestMode::Check => {
-                // In check mode, test vectors are not updated
+                // In check mode, test vectors are not updated, but every loaded entry must have been consumed
+                let unconsumed: Vec<(usize, TestVectorEntry)> = match tv_env.entry_reader.take() {
+                    Some(entry_reader) => entry_reader.remaining()?,
+                    None => tv_env
+                        .loaded_tv_data
+                        .entries
+                        .iter()
+                        .zip(&tv_env.consumed)
+                        .enumerate()
+                        .filter(|(_, (_, consumed))| !**consumed)
+                        .map(|(index, (entry, _))| (index, entry.clone()))
+                        .collect(),
+                };
+                let loaded = match (tv_env.loaded_tv_data.entries.len(), unconsumed.last()) {
+                    (0, Some((last_index, _))) => last_index + 1,
+                    (loaded, _) => loaded,
+                };
+                let entries: Vec<UnconsumedEntry> = unconsumed
+                    .into_iter()
+                    .map(|(index, entry)| UnconsumedEntry {
+                        index,
+                        name: entry.name,
+                        entry_type: entry.entry_type,
+                        code_location: entry.code_location,
+                    })
+                    .collect();
+                let mut failures = std::mem::take(&mut tv_env.deferred_failures);
+                if !entries.is_empty() && !tv_env.options.allow_unconsumed {
+                    let recorded = tv_env.observed_entries;
+                    failures.push(TestVectorError::UnconsumedEntries {
+                        recorded,
+                        loaded,
+                        entries,
+                    });
+                }
+                match (failures.len(), tv_env.options.failure_mode) {
+                    (0, _) => {}
+                    (1, FailureMode::Immediate) => return Err(failures.remove(0)),
+                    // deferred sessions always report the collected list, even of one failure
+                    _ => return Err(TestVectorError::DeferredFailures { failures }),
+                }
             }
-            TestMode::Init => {
-                // In both init mode, the test vector file is update if necessary
-                let update_required = tv_env.loaded_tv_data != tv_env.recorded_tv_data ||  // Test vectors have changed
+            TestMode::Init | TestMode::Update => {
+                if let Some(entry_writer) = tv_env.entry_writer.take() {
+                    // a streamed recording is complete, replace the file with it
+                    let previous_blobs = tv_env.stored_blobs();
+                    entry_writer.finish()?;
+                    remove_stale_blobs(&tv_env.tv_file_path, previous_blobs)?;
+                    remove_legacy_sidecars(&tv_env.tv_file_path).map_err(|source| {
+                        TestVectorError::Io {
+                            path: tv_env.tv_file_path.clone(),
+                            source,
+                        }
+                    })?;
+                    return Ok(());
+                }
+                // outputs are not replayed in update mode, decode them to detect changes; an
+                // output whose blob is missing or corrupted is re-recorded
+                let mut undecodable = false;
+                for entry_index in 0..tv_env.loaded_tv_data.entries.len() {
+                    if let Err(e) = tv_env.resolve_loaded_entry(entry_index) {
+                        match tv_env.loaded_tv_data.entries[entry_index].entry_type {
+                            TestVectorEntryType::Const => return Err(e),
+                            TestVectorEntryType::Output => undecodable = true,
+                        }
+                    }
+                }
+                // In both init and update mode, the test vector file is updated if necessary
+                // a header of another assert_tv or package version alone does not rewrite the file
+                let update_required = undecodable || // Stored outputs cannot be decoded
+                        tv_env.loaded_tv_data.entries != tv_env.recorded_tv_data.entries ||  // Test vectors have changed
                         !tv_env.tv_file_path.is_file(); // OR test vector file does not exist
                 if update_required {
+                    let mut previous_blobs = tv_env.stored_blobs();
                     tv_env
                         .recorded_tv_data
                         .store_to_file(&tv_env.tv_file_path, tv_env.file_format)?;
+                    for blob in tv_env.recorded_tv_data.referenced_blobs() {
+                        previous_blobs.remove(&blob);
+                    }
+                    // blobs only the previous file used are removed, shared ones are kept
+                    remove_stale_blobs(&tv_env.tv_file_path, previous_blobs)?;
+                    // the rewritten file references blobs only
+                    remove_legacy_sidecars(&tv_env.tv_file_path).map_err(|source| {
+                        TestVectorError::Io {
+                            path: tv_env.tv_file_path.clone(),
+                            source,
+                        }
+                    })?;
                 }
             }
+            TestMode::InitIfMissing => unreachable!("resolved when the session is initialized"),
         }
         Ok(())
     })
@@ -265,6 +815,37 @@ pub fn finalize_tv_case() -> anyhow::Result<()> {
 /// Low‑level: process the next observed entry.
 ///
 /// This is used internally by `TestVector::{expose_value, expose_mut_value, check_value}`.
+/// The field's serializers and options are read from `test_vec_field`.
+pub fn process_next_value<O>(
+    entry_type: TestVectorEntryType,
+    test_vec_field: &TestValue<O>,
+    observed_value: &O,
+    code_location: Option<String>,
+) -> Result<Option<O>, TestVectorError> {
+    let serializer =
+        test_vec_field
+            .serializer
+            .as_ref()
+            .ok_or_else(|| TestVectorError::Serialize {
+                name: test_vec_field.name.clone(),
+                source: anyhow!("Serializer was not provided for test field: {test_vec_field:?}"),
+            })?;
+    process_entry(
+        entry_type,
+        test_vec_field,
+        serializer,
+        test_vec_field.deserializer.as_ref(),
+        observed_value,
+        code_location,
+    )
+}
+
+/// Low‑level: process the next observed entry with the given serializers.
+///
+/// Only supports the field options passed here; per-field options such as tolerances or
+/// masks require `process_next_value`.
+#[deprecated(note = "use `process_next_value`, which takes the `TestValue` of the field")]
+#[allow(clippy::too_many_arguments)]
 pub fn process_next_entry<O>(
     entry_type: TestVectorEntryType,
     description: Option<String>,
@@ -276,21 +857,127 @@ pub fn process_next_entry<O>(
     deserializer: Option<&DynDeserializer<O>>,
     offload: bool,
 ) -> anyhow::Result<Option<O>> {
-    let value = serializer(observed_value)?;
-    let observed_entry = TestVectorEntry {
-        entry_type,
+    let test_vec_field = TestValue {
+        name,
         description,
+        test_value_field_code_location: test_vec_set_code_location.unwrap_or_default(),
+        offload,
+        ..Default::default()
+    };
+    process_entry(
+        entry_type,
+        &test_vec_field,
+        serializer,
+        deserializer,
+        observed_value,
+        code_location,
+    )
+    .map_err(anyhow::Error::from)
+}
+
+fn process_entry<O>(
+    entry_type: TestVectorEntryType,
+    test_vec_field: &TestValue<O>,
+    serializer: &DynSerializer<O>,
+    deserializer: Option<&DynDeserializer<O>>,
+    observed_value: &O,
+    code_location: Option<String>,
+) -> Result<Option<O>, TestVectorError> {
+    let name = test_vec_field.name.clone();
+    let mut value = serializer(observed_value).map_err(|source| TestVectorError::Serialize {
+        name: name.clone(),
+        source,
+    })?;
+    // constants are replayed to the code under test, so only outputs are sorted or masked
+    let (unordered, ignored) = match entry_type {
+        TestVectorEntryType::Output => (
+            parse_paths(&name, test_vec_field.unordered.as_deref())?,
+            parse_paths(&name, Some(&test_vec_field.ignore_paths))?,
+        ),
+        TestVectorEntryType::Const => (None, None),
+    };
+    let placeholder = test_vec_field
+        .redact
+        .as_deref()
+        .unwrap_or(DEFAULT_REDACTION);
+    let normalize = |value: &mut serde_json::Value| {
+        if let Some(paths) = &ignored {
+            mask_paths(value, paths, placeholder);
+        }
+        if let Some(paths) = &unordered {
+            sort_unordered_arrays(value, paths);
+        }
+    };
+    normalize(&mut value);
+    let mut observed_entry = TestVectorEntry {
+        entry_type,
+        description: test_vec_field.description.clone(),
         name,
         value,
         code_location,
-        test_vec_set_code_location,
-        offload,
+        test_vec_set_code_location: Some(test_vec_field.test_value_field_code_location.clone()),
+        offload: test_vec_field.offload,
+        offload_hash: None,
+        offload_len: None,
+        offload_codec: None,
+        offload_encoding: None,
+        compress: test_vec_field.compress,
     };
+    let deserialize = |value: &serde_json::Value| match deserializer {
+        Some(deserializer) => deserializer(value),
+        None => bail!("Deserializer was not provided for test field: {test_vec_field:?}"),
+    };
+    let compare_options = CompareOptions::of(test_vec_field);
+    let comparator = test_vec_field.comparator.as_ref().map(|comparator| {
+        move |loaded: &serde_json::Value| {
+            deserialize(loaded).map(|loaded| comparator(&loaded, observed_value))
+        }
+    });
 
     TestVecEnv::with_global(|tv_env| {
-        let entry_index = tv_env.recorded_tv_data.entries.len();
-        let loaded_entry = tv_env.loaded_tv_data.entries.get(entry_index).cloned();
-        tv_env.recorded_tv_data.entries.push(observed_entry.clone());
+        if let Some(offload_threshold) = test_vec_field
+            .offload_threshold
+            .or(tv_env.options.offload_threshold)
+        {
+            // measured like it is stored, as compact JSON
+            let serialized_len = serde_json::to_vec(&observed_entry.value)
+                .map_err(|e| TestVectorError::Serialize {
+                    name: observed_entry.name.clone(),
+                    source: e.into(),
+                })?
+                .len();
+            observed_entry.offload |= serialized_len > offload_threshold;
+        }
+        observed_entry.offload |= test_vec_field.bytes;
+        if observed_entry.offload {
+            observed_entry.offload_codec = Some(
+                test_vec_field
+                    .offload_codec
+                    .unwrap_or(tv_env.options.offload_codec),
+            );
+            // byte arrays decode to the identical JSON value, so they are always stored raw
+            if byte_array(&observed_entry.value).is_some() {
+                observed_entry.offload_encoding = Some(OffloadEncoding::Bytes);
+            } else if test_vec_field.bytes {
+                return Err(TestVectorError::Serialize {
+                    name: observed_entry.name.clone(),
+                    source: anyhow!(
+                        "`bytes` fields must serialize to an array of integers in 0..=255"
+                    ),
+                });
+            }
+        }
+        let mut loaded_entry = tv_env.next_loaded_entry(&observed_entry)?;
+        if let Some(loaded_entry) = &mut loaded_entry {
+            // files recorded before the field options changed are normalized as well
+            normalize(&mut loaded_entry.value);
+        }
+        tv_env.observed_entries += 1;
+        if let Some(entry_writer) = &mut tv_env.entry_writer {
+            entry_writer.append(observed_entry.clone())?;
+        } else if tv_env.entry_reader.is_none() {
+            tv_env.recorded_tv_data.entries.push(observed_entry.clone());
+        }
         match tv_env.test_mode {
             TestMode::Init => {
                 // init mode ignores (doesn't check) all entries (passes it through to be stored)
@@ -298,13 +985,15 @@ pub fn process_next_entry<O>(
                 // This is done to have exact same behaviour as check mode, where consts are loaded and replaced
                 match observed_entry.entry_type {
                     TestVectorEntryType::Const => Ok(Some(
-                        deserializer.expect("Deserializer was required but not provided")(
-                            &observed_entry.value,
-                        )
-                        .with_context(|| {
-                            "Failed to deserialize constant value right after serializing it. \
-                        There probably is a bug in the TestVectorMomento implementation"
-                        })?,
+                        deserialize(&observed_entry.value)
+                            .context(
+                                "Failed to deserialize constant value right after serializing it. \
+                        There probably is a bug in the TestVectorMomento implementation",
+                            )
+                            .map_err(|source| TestVectorError::Deserialize {
+                                name: observed_entry.name.clone(),
+                                source,
+                            })?,
                     )),
                     TestVectorEntryType::Output => {
                         // Nothing will be outputted if the entry type is output (as there is nothing to be replaced
@@ -313,97 +1002,190 @@ pub fn process_next_entry<O>(
                 }
             }
             TestMode::Check => {
-                let Some(loaded_entry) = loaded_entry else {
-                    bail!(
-                        "Observed value does not exist in loaded test vector: \n observed: {:?}",
-                        observed_entry
-                    )
-                };
-                let diff = || {
-                    format!(
-                        "\n\
-                                     loaded name: {:?}\n\
-                                   observed name: {:?}\n\
-                                    loaded value: {:?}\n\
-                                  observed value: {:?}\n\
-                                    loaded entry_type: {:?}\n\
-                                  observed entry_type: {:?}\n",
-                        loaded_entry.name,
-                        observed_entry.name,
-                        loaded_entry.value,
-                        observed_entry.value,
-                        loaded_entry.entry_type,
-                        observed_entry.entry_type
-                    )
-                };
-                // check entry types
-                match observed_entry.entry_type {
-                    TestVectorEntryType::Const | TestVectorEntryType::Output => {
-                        if loaded_entry.name != observed_entry.name {
-                            bail!(
-                                "Observed value does not match the loaded test vectors name:{}",
-                                diff()
-                            )
-                        }
-                        if loaded_entry.entry_type != observed_entry.entry_type {
-                            bail!(
-                                "Observed value does not match the loaded test vectors type:{}",
-                                diff()
-                            )
-                        }
+                let entry_type = observed_entry.entry_type;
+                match check_entry(
+                    loaded_entry,
+                    observed_entry,
+                    deserialize,
+                    &compare_options,
+                    comparator,
+                ) {
+                    Err(failure)
+                        if entry_type == TestVectorEntryType::Output
+                            && tv_env.options.failure_mode == FailureMode::Deferred =>
+                    {
+                        // soft assertion: remember the failure and keep executing
+                        tv_env.deferred_failures.push(failure);
+                        Ok(None)
                     }
+                    checked => checked,
                 }
-
-                // check the value if it is output
-                match loaded_entry.entry_type {
-                    TestVectorEntryType::Const => {}
-                    TestVectorEntryType::Output => {
-                        if loaded_entry.value != observed_entry.value {
-                            bail!(
-                                "Observed value does not match the loaded test vectors value:{}",
-                                diff()
-                            )
-                        }
+            }
+            TestMode::Update => match observed_entry.entry_type {
+                // outputs are re-recorded without comparing them to the stored values
+                TestVectorEntryType::Output => Ok(None),
+                TestVectorEntryType::Const => {
+                    let Some(loaded_entry) = loaded_entry else {
+                        // a constant that is not stored yet is recorded as in init mode
+                        return deserialize(&observed_entry.value)
+                            .map(Some)
+                            .map_err(|source| TestVectorError::Deserialize {
+                                name: observed_entry.name,
+                                source,
+                            });
+                    };
+                    let loaded_value = loaded_entry.value.clone();
+                    let value = check_entry(
+                        Some(loaded_entry),
+                        observed_entry,
+                        deserialize,
+                        &compare_options,
+                        comparator,
+                    )?;
+                    // keep the stored constant, so the rewritten file replays the same inputs
+                    if let Some(recorded_entry) = tv_env.recorded_tv_data.entries.last_mut() {
+                        recorded_entry.value = loaded_value;
                     }
-                };
-
-                // Deserialize const values
-                match loaded_entry.entry_type {
-                    TestVectorEntryType::Const => deserializer
-                        .expect("Deserializer was required but not provided")(
-                        &loaded_entry.value
-                    )
-                    .map(|v| Some(v)),
-                    TestVectorEntryType::Output => Ok(None),
+                    Ok(value)
                 }
-            }
+            },
+            TestMode::InitIfMissing => unreachable!("resolved when the session is initialized"),
         }
     })
 }
 
-fn is_false(v: &bool) -> bool {
-    !*v
+fn parse_paths(
+    name: &Option<String>,
+    paths: Option<&[String]>,
+) -> Result<Option<Vec<JsonPath>>, TestVectorError> {
+    paths
+        .map(|paths| {
+            paths
+                .iter()
+                .map(|path| {
+                    JsonPath::parse(path).map_err(|message| TestVectorError::InvalidPath {
+                        name: name.clone(),
+                        path: path.clone(),
+                        message,
+                    })
+                })
+                .collect()
+        })
+        .transpose()
 }
 
-fn append_suffix_to_filename(path: &PathBuf, suffix: &str) -> PathBuf {
-    let mut path = path.clone();
-    if let Some(file_name) = path.file_name().map(|f| f.to_string_lossy()) {
-        let new_file_name = format!("{}{}", file_name, suffix);
-        path.set_file_name(new_file_name);
+/// Validate an observed entry against its loaded counterpart in `Check` mode.
+///
+/// Returns the loaded (deserialized) value for constants and `None` for outputs.
+fn check_entry<O>(
+    loaded_entry: Option<TestVectorEntry>,
+    observed_entry: TestVectorEntry,
+    deserialize: impl Fn(&serde_json::Value) -> anyhow::Result<O>,
+    compare_options: &CompareOptions,
+    comparator: Option<impl Fn(&serde_json::Value) -> anyhow::Result<bool>>,
+) -> Result<Option<O>, TestVectorError> {
+    let Some(loaded_entry) = loaded_entry else {
+        return Err(TestVectorError::MissingEntry {
+            name: observed_entry.name,
+            entry_type: observed_entry.entry_type,
+            code_location: observed_entry.code_location,
+        });
+    };
+    // check entry types
+    match observed_entry.entry_type {
+        TestVectorEntryType::Const | TestVectorEntryType::Output => {
+            if loaded_entry.name != observed_entry.name {
+                return Err(TestVectorError::NameMismatch {
+                    loaded: loaded_entry.name,
+                    observed: observed_entry.name,
+                    code_location: observed_entry.code_location,
+                });
+            }
+            if loaded_entry.entry_type != observed_entry.entry_type {
+                return Err(TestVectorError::TypeMismatch {
+                    name: observed_entry.name,
+                    loaded: loaded_entry.entry_type,
+                    observed: observed_entry.entry_type,
+                    code_location: observed_entry.code_location,
+                });
+            }
+        }
     }
-    path
-}
 
-fn decompress(data: Vec<u8>) -> anyhow::Result<Vec<u8>> {
-    let cursor = Cursor::new(data);
-    let decompressed = zstd::decode_all(cursor)?;
-    Ok(decompressed)
+    // check the value if it is output
+    match loaded_entry.entry_type {
+        TestVectorEntryType::Const => {}
+        TestVectorEntryType::Output => {
+            let differences = match comparator {
+                Some(comparator) => {
+                    let equal = comparator(&loaded_entry.value).map_err(|source| {
+                        TestVectorError::Deserialize {
+                            name: loaded_entry.name.clone(),
+                            source,
+                        }
+                    })?;
+                    if equal {
+                        Vec::new()
+                    } else {
+                        // the diff only explains the mismatch, the comparator decides about it
+                        let differences = diff_values(
+                            &loaded_entry.value,
+                            &observed_entry.value,
+                            &CompareOptions::default(),
+                        );
+                        if differences.is_empty() {
+                            vec![ValueDifference::Changed {
+                                path: "$".to_string(),
+                                loaded: loaded_entry.value.clone(),
+                                observed: observed_entry.value.clone(),
+                            }]
+                        } else {
+                            differences
+                        }
+                    }
+                }
+                None => diff_values(&loaded_entry.value, &observed_entry.value, compare_options),
+            };
+            if let Some(first) = differences.first() {
+                let (loaded, observed) = match first {
+                    ValueDifference::Changed {
+                        loaded, observed, ..
+                    } => (loaded.clone(), observed.clone()),
+                    ValueDifference::Added { observed, .. } => {
+                        (serde_json::Value::Null, observed.clone())
+                    }
+                    ValueDifference::Removed { loaded, .. } => {
+                        (loaded.clone(), serde_json::Value::Null)
+                    }
+                };
+                return Err(TestVectorError::ValueMismatch {
+                    name: observed_entry.name,
+                    code_location: observed_entry.code_location,
+                    path: first.path().to_string(),
+                    loaded: Box::new(loaded),
+                    observed: Box::new(observed),
+                    differences,
+                });
+            }
+        }
+    };
+
+    // Deserialize const values
+    match loaded_entry.entry_type {
+        TestVectorEntryType::Const => {
+            deserialize(&loaded_entry.value)
+                .map(Some)
+                .map_err(|source| TestVectorError::Deserialize {
+                    name: loaded_entry.name,
+                    source,
+                })
+        }
+        TestVectorEntryType::Output => Ok(None),
+    }
 }
 
-fn compress(data: Vec<u8>) -> anyhow::Result<Vec<u8>> {
-    let cursor = Cursor::new(data);
-    let compressed = zstd::encode_all(cursor, 15)?;
-    Ok(compressed)
+fn is_false(v: &bool) -> bool {
+    !*v
 }
 
 fn is_null(value: &serde_json::Value) -> bool {
@@ -0,0 +1,46 @@
+//! Fixtures shared by the integration tests.
+#![allow(dead_code)]
+
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+/// Temporary directory of a test, removed when dropped, also if the test panics.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Create an empty directory unique to `name` and the test process.
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("assert_tv_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// A JSON test vector file in its own temporary directory, which must be kept alive while
+/// the file is used.
+pub fn tv_file(name: &str) -> (TempDir, PathBuf) {
+    let dir = TempDir::new(name);
+    let tv_file_path = dir.join(format!("{}.json", name));
+    (dir, tv_file_path)
+}
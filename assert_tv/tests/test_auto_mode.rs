@@ -0,0 +1,52 @@
+mod common;
+
+use assert_tv::{
+    finalize_tv_case, initialize_tv_case_from_file, TestMode, TestValue, TestVector,
+    TestVectorActive, TestVectorError, TestVectorFileFormat, TestVectorSet,
+};
+use common::tv_file;
+use std::path::Path;
+
+#[derive(TestVectorSet)]
+struct Fields {
+    #[test_vec(name = "input")]
+    input: TestValue<u64>,
+}
+
+fn run_auto(tv_file_path: &Path, input: u64) -> Result<u64, TestVectorError> {
+    let guard = initialize_tv_case_from_file(
+        tv_file_path,
+        TestVectorFileFormat::Json,
+        TestMode::InitIfMissing,
+    )?;
+    let fields = TestVectorActive::initialize_values::<Fields>();
+    let input = TestVectorActive::expose_value(&fields.input, input);
+    finalize_tv_case()?;
+    drop(guard);
+    Ok(input)
+}
+
+// A single test in this binary, as it modifies the process environment.
+#[test]
+fn test_init_if_missing() {
+    let (_dir, tv_file_path) = tv_file("auto_mode");
+
+    // creating the file is forbidden in CI
+    std::env::set_var("TEST_VECTORS_CI", "1");
+    let err = run_auto(&tv_file_path, 1).unwrap_err();
+    assert!(
+        matches!(err, TestVectorError::FileCreationForbidden { .. }),
+        "unexpected error: {err}"
+    );
+    assert!(!tv_file_path.exists());
+    std::env::remove_var("TEST_VECTORS_CI");
+
+    // a missing file is initialized
+    assert_eq!(run_auto(&tv_file_path, 1).unwrap(), 1);
+    assert!(tv_file_path.is_file());
+
+    // an existing file is checked, also in CI
+    std::env::set_var("TEST_VECTORS_CI", "1");
+    assert_eq!(run_auto(&tv_file_path, 2).unwrap(), 1);
+    std::env::remove_var("TEST_VECTORS_CI");
+}
@@ -0,0 +1,215 @@
+mod common;
+
+use assert_tv::{
+    finalize_tv_case, initialize_tv_case_from_file, TestMode, TestValue, TestVector,
+    TestVectorActive, TestVectorFileFormat, TestVectorSet,
+};
+use common::tv_file;
+use std::collections::HashSet;
+
+/// Records `record` in init mode, then checks `replay` against it.
+fn record_and_check(
+    name: &str,
+    record: impl Fn(),
+    replay: impl Fn() + std::panic::UnwindSafe,
+) -> bool {
+    let (_dir, tv_file_path) = tv_file(name);
+    let guard =
+        initialize_tv_case_from_file(&tv_file_path, TestVectorFileFormat::Json, TestMode::Init)
+            .unwrap();
+    record();
+    finalize_tv_case().unwrap();
+    drop(guard);
+
+    let guard =
+        initialize_tv_case_from_file(&tv_file_path, TestVectorFileFormat::Json, TestMode::Check)
+            .unwrap();
+    let passed = std::panic::catch_unwind(replay).is_ok();
+    drop(guard);
+    passed
+}
+
+#[derive(TestVectorSet)]
+struct ToleranceFields {
+    #[test_vec(name = "mean", tolerance = 1e-9)]
+    mean: TestValue<Vec<f64>>,
+    #[test_vec(name = "energy", rel_tolerance = 1e-6)]
+    energy: TestValue<(f64, u32)>,
+}
+
+fn physics<TV: TestVector>(noise: f64) {
+    let fields = TV::initialize_values::<ToleranceFields>();
+    TV::check_value(&fields.mean, &vec![0.1 + 0.2 + noise, 1.0]);
+    TV::check_value(&fields.energy, &(1e10 * (1.0 + noise * 1e3), 7));
+}
+
+#[test]
+fn test_tolerance_attributes() {
+    assert!(record_and_check(
+        "tolerance_within",
+        || physics::<TestVectorActive>(0.0),
+        || physics::<TestVectorActive>(1e-12),
+    ));
+    assert!(!record_and_check(
+        "tolerance_beyond",
+        || physics::<TestVectorActive>(0.0),
+        || physics::<TestVectorActive>(1e-6),
+    ));
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Event {
+    kind: String,
+    timestamp: u64,
+}
+
+// comparators receive `&T`, so the signature is fixed by the field type
+#[allow(clippy::ptr_arg)]
+fn same_members(loaded: &Vec<u32>, observed: &Vec<u32>) -> bool {
+    let mut loaded = loaded.clone();
+    let mut observed = observed.clone();
+    loaded.sort();
+    observed.sort();
+    loaded == observed
+}
+
+fn same_kind(loaded: &Event, observed: &Event) -> bool {
+    loaded.kind == observed.kind
+}
+
+#[derive(TestVectorSet)]
+struct ComparatorFields {
+    #[test_vec(name = "members", compare_with = "same_members")]
+    members: TestValue<Vec<u32>>,
+    #[test_vec(name = "event", compare_with = "same_kind")]
+    event: TestValue<Event>,
+}
+
+fn events<TV: TestVector>(members: Vec<u32>, kind: &str, timestamp: u64) {
+    let fields = TV::initialize_values::<ComparatorFields>();
+    TV::check_value(&fields.members, &members);
+    TV::check_value(
+        &fields.event,
+        &Event {
+            kind: kind.to_string(),
+            timestamp,
+        },
+    );
+}
+
+#[test]
+fn test_compare_with_attribute() {
+    assert!(record_and_check(
+        "comparator_equal",
+        || events::<TestVectorActive>(vec![1, 2, 3], "start", 100),
+        || events::<TestVectorActive>(vec![3, 1, 2], "start", 200),
+    ));
+    assert!(!record_and_check(
+        "comparator_members",
+        || events::<TestVectorActive>(vec![1, 2, 3], "start", 100),
+        || events::<TestVectorActive>(vec![1, 2, 4], "start", 100),
+    ));
+    assert!(!record_and_check(
+        "comparator_kind",
+        || events::<TestVectorActive>(vec![1, 2, 3], "start", 100),
+        || events::<TestVectorActive>(vec![1, 2, 3], "stop", 100),
+    ));
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Group {
+    order: Vec<u32>,
+    members: Vec<u32>,
+}
+
+#[derive(TestVectorSet)]
+struct UnorderedFields {
+    #[test_vec(name = "seen", unordered)]
+    seen: TestValue<HashSet<String>>,
+    #[test_vec(name = "groups", unordered = ["$[*].members"])]
+    groups: TestValue<Vec<Group>>,
+}
+
+fn parallel_work<TV: TestVector>(reverse: bool, order: Vec<u32>) {
+    let fields = TV::initialize_values::<UnorderedFields>();
+    let mut members: Vec<u32> = (0..20).collect();
+    if reverse {
+        members.reverse();
+    }
+    let seen = members.iter().map(|m| format!("member-{m}")).collect();
+    TV::check_value(&fields.seen, &seen);
+    TV::check_value(&fields.groups, &vec![Group { order, members }]);
+}
+
+#[derive(TestVectorSet)]
+struct UnorderedInput {
+    #[test_vec(name = "input", unordered)]
+    input: TestValue<Vec<u32>>,
+}
+
+fn expose_input(input: Vec<u32>) -> Vec<u32> {
+    let fields = TestVectorActive::initialize_values::<UnorderedInput>();
+    TestVectorActive::expose_value(&fields.input, input)
+}
+
+#[test]
+fn test_unordered_attribute() {
+    assert!(record_and_check(
+        "unordered_equal",
+        || parallel_work::<TestVectorActive>(false, vec![1, 2]),
+        || parallel_work::<TestVectorActive>(true, vec![1, 2]),
+    ));
+    assert!(!record_and_check(
+        "unordered_unselected",
+        || parallel_work::<TestVectorActive>(false, vec![1, 2]),
+        || parallel_work::<TestVectorActive>(false, vec![2, 1]),
+    ));
+
+    // constants are replayed in their original order
+    let (_dir, tv_file_path) = tv_file("unordered_const");
+    for (mode, input) in [(TestMode::Init, vec![3, 1, 2]), (TestMode::Check, vec![])] {
+        let guard =
+            initialize_tv_case_from_file(&tv_file_path, TestVectorFileFormat::Json, mode).unwrap();
+        assert_eq!(expose_input(input), vec![3, 1, 2]);
+        finalize_tv_case().unwrap();
+        drop(guard);
+    }
+}
+
+#[derive(TestVectorSet)]
+struct MaskedFields {
+    #[test_vec(name = "response", ignore_paths = ["$.id", "$.meta.ts"], redact = "<volatile>")]
+    response: TestValue<serde_json::Value>,
+}
+
+fn respond<TV: TestVector>(id: u64, status: &str) {
+    let fields = TV::initialize_values::<MaskedFields>();
+    TV::check_value(
+        &fields.response,
+        &serde_json::json!({"id": id, "meta": {"ts": id * 10, "status": status}}),
+    );
+}
+
+#[test]
+fn test_ignore_paths_attribute() {
+    let (_dir, tv_file_path) = tv_file("ignore_paths_stored");
+    let guard =
+        initialize_tv_case_from_file(&tv_file_path, TestVectorFileFormat::Json, TestMode::Init)
+            .unwrap();
+    respond::<TestVectorActive>(1, "ok");
+    finalize_tv_case().unwrap();
+    drop(guard);
+    let stored = std::fs::read_to_string(&tv_file_path).unwrap();
+    assert_eq!(stored.matches("<volatile>").count(), 2, "{stored}");
+
+    assert!(record_and_check(
+        "ignore_paths_equal",
+        || respond::<TestVectorActive>(1, "ok"),
+        || respond::<TestVectorActive>(2, "ok"),
+    ));
+    assert!(!record_and_check(
+        "ignore_paths_status",
+        || respond::<TestVectorActive>(1, "ok"),
+        || respond::<TestVectorActive>(1, "failed"),
+    ));
+}
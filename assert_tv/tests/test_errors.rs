@@ -0,0 +1,203 @@
+mod common;
+
+use assert_tv::{
+    finalize_tv_case, initialize_tv_case_from_file, initialize_tv_case_with_options,
+    process_next_value, DynSerializer, FailureMode, TestMode, TestValue, TestVector,
+    TestVectorActive, TestVectorEntryType, TestVectorError, TestVectorFileFormat,
+    TestVectorOptions, TestVectorSet, ValueDifference,
+};
+use common::tv_file;
+use serde_json::{json, Value};
+
+fn check_output(name: &str, value: Value) -> Result<(), TestVectorError> {
+    let serializer: DynSerializer<Value> = Box::new(|v| Ok(v.clone()));
+    let field = TestValue::named(name, Some(serializer), None);
+    process_next_value(TestVectorEntryType::Output, &field, &value, None).map(|_| ())
+}
+
+#[test]
+fn test_missing_file_is_io_error() {
+    let (_dir, tv_file_path) = tv_file("does_not_exist");
+    let err =
+        initialize_tv_case_from_file(tv_file_path, TestVectorFileFormat::Json, TestMode::Check)
+            .err()
+            .expect("loading a missing file must fail");
+    assert!(
+        matches!(&err, TestVectorError::Io { source, .. } if source.kind() == std::io::ErrorKind::NotFound),
+        "unexpected error: {err}"
+    );
+}
+
+#[test]
+fn test_mismatches_are_classified() {
+    let (_dir, tv_file_path) = tv_file("classified");
+    let guard =
+        initialize_tv_case_from_file(&tv_file_path, TestVectorFileFormat::Json, TestMode::Init)
+            .unwrap();
+    check_output("out", json!({"items": [1, 2], "total": 3})).unwrap();
+    finalize_tv_case().unwrap();
+    drop(guard);
+
+    let guard =
+        initialize_tv_case_from_file(&tv_file_path, TestVectorFileFormat::Json, TestMode::Check)
+            .unwrap();
+    let err = check_output("out", json!({"items": [1, 5], "total": 3})).unwrap_err();
+    match err {
+        TestVectorError::ValueMismatch {
+            path,
+            loaded,
+            observed,
+            differences,
+            ..
+        } => {
+            assert_eq!(path, "$.items[1]");
+            assert_eq!(*loaded, json!(2));
+            assert_eq!(*observed, json!(5));
+            assert_eq!(
+                differences,
+                vec![ValueDifference::Changed {
+                    path: "$.items[1]".to_string(),
+                    loaded: json!(2),
+                    observed: json!(5),
+                }]
+            );
+        }
+        err => panic!("expected value mismatch, got: {err}"),
+    }
+    let err = check_output("out", json!(null)).unwrap_err();
+    assert!(
+        matches!(err, TestVectorError::MissingEntry { .. }),
+        "unexpected error: {err}"
+    );
+    drop(guard);
+
+    let guard =
+        initialize_tv_case_from_file(&tv_file_path, TestVectorFileFormat::Json, TestMode::Check)
+            .unwrap();
+    let err = check_output("renamed", json!(null)).unwrap_err();
+    assert!(
+        matches!(&err, TestVectorError::NameMismatch { loaded: Some(loaded), .. } if loaded == "out"),
+        "unexpected error: {err}"
+    );
+    drop(guard);
+}
+
+#[test]
+#[allow(deprecated)]
+fn test_deprecated_process_next_entry_forwards() {
+    let (_dir, tv_file_path) = tv_file("deprecated");
+    let serializer: DynSerializer<Value> = Box::new(|v| Ok(v.clone()));
+    let check = |value: Value| {
+        assert_tv::process_next_entry(
+            TestVectorEntryType::Output,
+            None,
+            Some("out".to_string()),
+            &value,
+            None,
+            None,
+            &serializer,
+            None,
+            false,
+        )
+    };
+    let guard =
+        initialize_tv_case_from_file(&tv_file_path, TestVectorFileFormat::Json, TestMode::Init)
+            .unwrap();
+    check(json!(1)).unwrap();
+    finalize_tv_case().unwrap();
+    drop(guard);
+
+    let guard =
+        initialize_tv_case_from_file(&tv_file_path, TestVectorFileFormat::Json, TestMode::Check)
+            .unwrap();
+    let err = check(json!(2)).unwrap_err();
+    assert!(
+        matches!(
+            err.downcast_ref::<TestVectorError>(),
+            Some(TestVectorError::ValueMismatch { .. })
+        ),
+        "unexpected error: {err}"
+    );
+    drop(guard);
+}
+
+#[derive(TestVectorSet)]
+struct DeferredFields {
+    #[test_vec(name = "seed")]
+    seed: TestValue<u64>,
+    #[test_vec(name = "double")]
+    double: TestValue<u64>,
+    #[test_vec(name = "square")]
+    square: TestValue<u64>,
+}
+
+fn deferred_computation<TV: TestVector>(seed: u64, offset: u64) -> u64 {
+    let fields = TV::initialize_values::<DeferredFields>();
+    let seed = TV::expose_value(&fields.seed, seed);
+    TV::check_value(&fields.double, &(seed * 2 + offset));
+    TV::check_value(&fields.square, &(seed * seed + offset));
+    seed
+}
+
+#[test]
+fn test_deferred_failures_are_collected() {
+    let (_dir, tv_file_path) = tv_file("deferred");
+    let guard =
+        initialize_tv_case_from_file(&tv_file_path, TestVectorFileFormat::Json, TestMode::Init)
+            .unwrap();
+    deferred_computation::<TestVectorActive>(3, 0);
+    finalize_tv_case().unwrap();
+    drop(guard);
+
+    let guard = initialize_tv_case_with_options(
+        &tv_file_path,
+        TestVectorFileFormat::Json,
+        TestMode::Check,
+        TestVectorOptions {
+            failure_mode: FailureMode::Deferred,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    // both outputs mismatch, execution continues with the loaded constant
+    let seed = deferred_computation::<TestVectorActive>(100, 1);
+    assert_eq!(seed, 3);
+    let err = finalize_tv_case().unwrap_err();
+    let TestVectorError::DeferredFailures { failures } = err else {
+        panic!("expected deferred failures, got: {err}");
+    };
+    let names: Vec<Option<&str>> = failures
+        .iter()
+        .map(|failure| match failure {
+            TestVectorError::ValueMismatch { name, .. } => name.as_deref(),
+            failure => panic!("expected value mismatch, got: {failure}"),
+        })
+        .collect();
+    assert_eq!(names, vec![Some("double"), Some("square")]);
+    drop(guard);
+
+    // a single mismatch is reported as a list as well
+    let guard = initialize_tv_case_with_options(
+        &tv_file_path,
+        TestVectorFileFormat::Json,
+        TestMode::Check,
+        TestVectorOptions {
+            failure_mode: FailureMode::Deferred,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let fields = TestVectorActive::initialize_values::<DeferredFields>();
+    let seed = TestVectorActive::expose_value(&fields.seed, 100);
+    TestVectorActive::check_value(&fields.double, &(seed * 2));
+    TestVectorActive::check_value(&fields.square, &0);
+    let err = finalize_tv_case().unwrap_err();
+    assert!(
+        matches!(
+            &err,
+            TestVectorError::DeferredFailures { failures } if failures.len() == 1
+        ),
+        "unexpected error: {err}"
+    );
+    drop(guard);
+}
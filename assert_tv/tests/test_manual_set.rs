@@ -1,11 +1,14 @@
+mod common;
+
 use assert_tv::{
     initialize_tv_case_from_file, TestMode, TestValue, TestVector, TestVectorActive,
     TestVectorFileFormat, TestVectorNOP,
 };
+use base64::prelude::{Engine, BASE64_STANDARD};
+use common::TempDir;
 use rand::Rng;
 use serde_json::{Map, Value};
 use std::io::Read;
-use std::path::Path;
 use std::str::FromStr;
 
 fn custom_serialize_fn(value: &u64) -> anyhow::Result<serde_json::Value> {
@@ -42,7 +45,7 @@ impl assert_tv::TestVectorSet for SomeTestFields {
                 },
                 compress: true,
                 offload: false,
-                _data_marker: std::default::Default::default(),
+                ..std::default::Default::default()
             },
             b: assert_tv::TestValue {
                 name: None,
@@ -56,7 +59,7 @@ impl assert_tv::TestVectorSet for SomeTestFields {
                 })),
                 compress: true,
                 offload: true,
-                _data_marker: std::default::Default::default(),
+                ..std::default::Default::default()
             },
         }
     }
@@ -84,13 +87,16 @@ fn some_other_functionality<TV: TestVector>(a: u64) {
 
 #[test]
 fn test_manual_set() {
+    // the offloaded `b` is written to a blob directory next to the test vector file
+    let tv_dir = TempDir::new("manual");
+    let tv_file_path = tv_dir.join("manual_tv.toml");
     let _guard = TestVectorActive::initialize_test_vector(
-        "manual_tv.toml",
+        &tv_file_path,
         TestVectorFileFormat::Toml,
         TestMode::Init,
     );
     let should_be_err = initialize_tv_case_from_file(
-        "manual_tv.toml",
+        &tv_file_path,
         TestVectorFileFormat::Toml,
         TestMode::Init,
     );
@@ -104,8 +110,7 @@ fn test_manual_set() {
     assert_tv::finalize_tv_case().expect("Error finalizing test vector case");
     drop(_guard);
 
-    let tv_file_path = Path::new("manual_tv.toml");
-    let mut tv_file = std::fs::File::open(tv_file_path)
+    let mut tv_file = std::fs::File::open(&tv_file_path)
         .map_err(|e| {
             anyhow::anyhow!(
                 "Failed to open test vector file ({:?}): {}",
@@ -133,13 +138,18 @@ fn test_manual_set() {
         a_entry.get("name").unwrap().as_str().unwrap(),
         "a".to_string()
     );
-    let a_value: &str = a_entry.get("value").unwrap().as_str().unwrap();
-    let captured_a_value: u64 = u64::from_str(a_value).unwrap();
+    // `a` is stored inline as a base64 encoded, zstd compressed JSON string
+    let a_compressed = BASE64_STANDARD
+        .decode(a_entry.get("value").unwrap().as_str().unwrap())
+        .unwrap();
+    let a_value: Value =
+        serde_json::from_slice(&zstd::decode_all(&a_compressed[..]).unwrap()).unwrap();
+    let captured_a_value: u64 = u64::from_str(a_value.as_str().unwrap()).unwrap();
     assert_eq!(a, captured_a_value);
 
     // test in check mode
     let _guard = TestVectorActive::initialize_test_vector(
-        "manual_tv.toml",
+        &tv_file_path,
         TestVectorFileFormat::Toml,
         TestMode::Check,
     );
@@ -152,7 +162,4 @@ fn test_manual_set() {
     let a = some_functionality_with_internal_randomness::<TestVectorNOP>();
     assert_ne!(a, captured_a_value); // a should (with extreme likelihood) not be the same value that was exposed
     some_other_functionality::<TestVectorNOP>(a);
-
-    // delete manual_tv.toml
-    // std::fs::remove_file(tv_file_path).unwrap()
 }
@@ -0,0 +1,79 @@
+mod common;
+
+use assert_tv::{
+    finalize_tv_case, initialize_tv_case_from_file, initialize_tv_case_with_options,
+    MatchingStrategy, TestMode, TestValue, TestVector, TestVectorActive, TestVectorFileFormat,
+    TestVectorOptions, TestVectorSet,
+};
+use common::tv_file;
+use std::path::Path;
+
+#[derive(TestVectorSet)]
+struct FirstFields {
+    #[test_vec(name = "seed")]
+    seed: TestValue<u64>,
+    #[test_vec(name = "out")]
+    out: TestValue<u64>,
+}
+
+#[derive(TestVectorSet)]
+struct SecondFields {
+    #[test_vec(name = "seed")]
+    seed: TestValue<u64>,
+}
+
+fn first<TV: TestVector>(seed: u64) -> u64 {
+    let fields = TV::initialize_values::<FirstFields>();
+    let seed = TV::expose_value(&fields.seed, seed);
+    let out = seed * 2;
+    TV::check_value(&fields.out, &out);
+    out
+}
+
+fn second<TV: TestVector>(seed: u64) -> u64 {
+    let fields = TV::initialize_values::<SecondFields>();
+    TV::expose_value(&fields.seed, seed)
+}
+
+fn check_swapped(tv_file_path: &Path, matching: MatchingStrategy) -> anyhow::Result<(u64, u64)> {
+    let guard = initialize_tv_case_with_options(
+        tv_file_path,
+        TestVectorFileFormat::Json,
+        TestMode::Check,
+        TestVectorOptions {
+            matching,
+            ..Default::default()
+        },
+    )?;
+    let result = std::panic::catch_unwind(|| {
+        let b = second::<TestVectorActive>(1000);
+        let a = first::<TestVectorActive>(1000);
+        let a_again = first::<TestVectorActive>(1000);
+        (a + a_again, b)
+    })
+    .map_err(|_| anyhow::anyhow!("check panicked"))?;
+    finalize_tv_case()?;
+    drop(guard);
+    Ok(result)
+}
+
+#[test]
+fn test_keyed_matching_tolerates_reordered_code_paths() {
+    let (_dir, tv_file_path) = tv_file("keyed_matching");
+    let guard =
+        initialize_tv_case_from_file(&tv_file_path, TestVectorFileFormat::Json, TestMode::Init)
+            .unwrap();
+    first::<TestVectorActive>(1);
+    second::<TestVectorActive>(7);
+    first::<TestVectorActive>(2);
+    finalize_tv_case().unwrap();
+    drop(guard);
+
+    // the calls are reordered, positional matching pairs `second` with the first entry
+    assert!(check_swapped(&tv_file_path, MatchingStrategy::Positional).is_err());
+
+    // keyed matching pairs entries by field set, name and occurrence
+    let (a, b) = check_swapped(&tv_file_path, MatchingStrategy::Keyed).unwrap();
+    assert_eq!(a, 2 + 4);
+    assert_eq!(b, 7);
+}
@@ -0,0 +1,678 @@
+mod common;
+
+use assert_tv::{
+    finalize_tv_case, initialize_tv_case_from_file, initialize_tv_case_with_options,
+    process_next_value, sweep_orphaned_offloads, OffloadCodec, TestMode, TestValue, TestVector,
+    TestVectorActive, TestVectorEntryType, TestVectorError, TestVectorFileFormat,
+    TestVectorOptions, TestVectorSet,
+};
+use common::{tv_file, TempDir};
+use std::path::Path;
+
+fn run<F: TestVectorSet, R>(tv_file_path: &Path, mode: TestMode, body: impl FnOnce(F) -> R) -> R {
+    let guard =
+        initialize_tv_case_from_file(tv_file_path, TestVectorFileFormat::Json, mode).unwrap();
+    let result = body(TestVectorActive::initialize_values::<F>());
+    finalize_tv_case().unwrap();
+    drop(guard);
+    result
+}
+
+#[derive(TestVectorSet)]
+struct CompressedFields {
+    #[test_vec(name = "samples", compress = true)]
+    samples: TestValue<Vec<u32>>,
+    #[test_vec(name = "histogram", compress = true)]
+    histogram: TestValue<Vec<u32>>,
+}
+
+#[test]
+fn test_compress_stores_inline() {
+    let (_dir, tv_file_path) = tv_file("compress");
+    let samples: Vec<u32> = (0..1000).map(|i| i % 7).collect();
+    let run_case = |mode, samples: Vec<u32>| {
+        run(&tv_file_path, mode, |fields: CompressedFields| {
+            let samples = TestVectorActive::expose_value(&fields.samples, samples);
+            let mut histogram = vec![0; 7];
+            for sample in &samples {
+                histogram[*sample as usize] += 1;
+            }
+            TestVectorActive::check_value(&fields.histogram, &histogram);
+            samples
+        })
+    };
+    run_case(TestMode::Init, samples.clone());
+
+    let stored: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(&tv_file_path).unwrap()).unwrap();
+    for entry in stored["entries"].as_array().unwrap() {
+        assert_eq!(entry["compress"], true);
+        assert!(entry["value"].is_string(), "{entry}");
+    }
+    assert!(stored.to_string().len() < 1000, "{stored}");
+
+    // check mode decompresses the constant and the output transparently
+    assert_eq!(run_case(TestMode::Check, Vec::new()), samples);
+}
+
+#[derive(TestVectorSet)]
+struct OffloadedFields {
+    #[test_vec(name = "first", offload = true)]
+    first: TestValue<Vec<u64>>,
+    #[test_vec(name = "second", offload = true)]
+    second: TestValue<Vec<u64>>,
+}
+
+fn blob_names(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = std::fs::read_dir(dir.join("blobs"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn test_offloaded_values_are_content_addressed() {
+    let dir = TempDir::new("blobs");
+    let first_file = dir.join("first.json");
+    let second_file = dir.join("second.json");
+    let shared: Vec<u64> = (0..100).collect();
+
+    let offload_twice = |tv_file_path: &Path, mode, first: Vec<u64>, second: Vec<u64>| {
+        run(tv_file_path, mode, |fields: OffloadedFields| {
+            (
+                TestVectorActive::expose_value(&fields.first, first),
+                TestVectorActive::expose_value(&fields.second, second),
+            )
+        })
+    };
+    offload_twice(&first_file, TestMode::Init, shared.clone(), shared.clone());
+    let blobs = blob_names(&dir);
+    assert_eq!(blobs.len(), 1, "identical values share a blob: {blobs:?}");
+    // arrays of small integers are stored as raw bytes
+    assert!(blobs[0].ends_with(".bin.zst") && blobs[0].len() == 64 + 8);
+
+    // another test vector file in the same directory reuses the blob
+    offload_twice(&second_file, TestMode::Init, vec![1], shared.clone());
+    assert_eq!(blob_names(&dir).len(), 2);
+    assert!(blob_names(&dir).contains(&blobs[0]));
+
+    assert_eq!(
+        offload_twice(&first_file, TestMode::Check, vec![], vec![]),
+        (shared.clone(), shared.clone())
+    );
+    assert_eq!(
+        offload_twice(&second_file, TestMode::Check, vec![], vec![]),
+        (vec![1], shared)
+    );
+}
+
+#[test]
+fn test_orphaned_offloads_are_removed() {
+    let dir = TempDir::new("sweep");
+    let tv_file_path = dir.join("case.json");
+    let legacy_sidecar = dir.join("case.json_offloaded_value_0.zstd");
+    let foreign_sidecar = dir.join("gone.json_offloaded_value_3.zstd");
+    std::fs::write(&legacy_sidecar, b"stale").unwrap();
+    std::fs::write(&foreign_sidecar, b"stale").unwrap();
+
+    let offload = |tv_file_path: &Path, first: Vec<u64>, second: Vec<u64>| {
+        run(tv_file_path, TestMode::Init, |fields: OffloadedFields| {
+            TestVectorActive::expose_value(&fields.first, first);
+            TestVectorActive::expose_value(&fields.second, second);
+        })
+    };
+    offload(&tv_file_path, vec![1, 2, 3], vec![7]);
+    // rewriting the file in init mode removes its own index-named sidecars
+    assert!(!legacy_sidecar.exists());
+    assert!(foreign_sidecar.exists());
+    let old_blobs = blob_names(&dir);
+    assert_eq!(old_blobs.len(), 2);
+
+    let other_file_path = dir.join("other.json");
+    offload(&other_file_path, vec![1, 2, 3], vec![9]);
+    let other = std::fs::read_to_string(&other_file_path).unwrap();
+    let (shared_blob, stale_blob): (Vec<_>, Vec<_>) = old_blobs
+        .iter()
+        .map(|blob| dir.join("blobs").join(blob))
+        .partition(|blob| other.contains(&blob.file_name().unwrap().to_str().unwrap()[..64]));
+    let (shared_blob, stale_blob) = (&shared_blob[0], &stale_blob[0]);
+
+    // finalize removes the blobs the rewritten file no longer references, unless shared
+    offload(&tv_file_path, vec![4, 5, 6], vec![8]);
+    assert!(!stale_blob.exists());
+    assert!(shared_blob.exists());
+    assert_eq!(blob_names(&dir).len(), 4);
+
+    // files that cannot be parsed are reported, and blobs they mention are kept
+    std::fs::remove_file(&other_file_path).unwrap();
+    let notes = dir.join("notes.json");
+    std::fs::write(&notes, format!("see {}", shared_blob.display())).unwrap();
+    let sweep = sweep_orphaned_offloads(&dir).unwrap();
+    assert_eq!(sweep.removed.len(), 2);
+    assert!(sweep.removed.contains(&foreign_sidecar));
+    assert_eq!(sweep.skipped.len(), 1);
+    assert_eq!(sweep.skipped[0].0, notes);
+    assert!(shared_blob.exists());
+
+    std::fs::remove_file(notes).unwrap();
+    assert_eq!(
+        sweep_orphaned_offloads(&dir).unwrap().removed,
+        vec![shared_blob.clone()]
+    );
+    assert_eq!(blob_names(&dir).len(), 2);
+    let sweep = sweep_orphaned_offloads(&dir).unwrap();
+    assert!(sweep.removed.is_empty() && sweep.skipped.is_empty());
+
+    // the remaining blobs are still sufficient to check the file
+    let replayed = run(&tv_file_path, TestMode::Check, |fields: OffloadedFields| {
+        (
+            TestVectorActive::expose_value(&fields.first, vec![]),
+            TestVectorActive::expose_value(&fields.second, vec![]),
+        )
+    });
+    assert_eq!(replayed, (vec![4, 5, 6], vec![8]));
+}
+
+#[derive(TestVectorSet)]
+struct ThresholdFields {
+    #[test_vec(name = "session")]
+    session: TestValue<Vec<u64>>,
+    #[test_vec(name = "field", offload_threshold = 1000)]
+    field: TestValue<Vec<u64>>,
+}
+
+#[test]
+fn test_offload_threshold() {
+    let dir = TempDir::new("threshold");
+    let tv_file_path = dir.join("case.json");
+    let record = |len: u64| {
+        let guard = initialize_tv_case_with_options(
+            &tv_file_path,
+            TestVectorFileFormat::Json,
+            TestMode::Init,
+            TestVectorOptions {
+                offload_threshold: Some(100),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let fields = TestVectorActive::initialize_values::<ThresholdFields>();
+        TestVectorActive::check_value(&fields.session, &(0..len).collect());
+        TestVectorActive::check_value(&fields.field, &(0..len).collect());
+        finalize_tv_case().unwrap();
+        drop(guard);
+        let stored: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&tv_file_path).unwrap()).unwrap();
+        let offloaded = |index: usize| stored["entries"][index]["offload"] == true;
+        (offloaded(0), offloaded(1))
+    };
+    assert_eq!(record(10), (false, false));
+    assert_eq!(record(100), (true, false));
+    assert_eq!(record(1000), (true, true));
+}
+
+#[test]
+fn test_offloaded_values_are_verified() {
+    let dir = TempDir::new("integrity");
+    let tv_file_path = dir.join("case.json");
+    let other_file_path = dir.join("other.json");
+    let offload = |tv_file_path: &Path, first: Vec<u64>| {
+        run(tv_file_path, TestMode::Init, |fields: OffloadedFields| {
+            TestVectorActive::expose_value(&fields.first, first);
+            TestVectorActive::expose_value(&fields.second, vec![]);
+        })
+    };
+    offload(&tv_file_path, vec![1, 2, 3]);
+    offload(&other_file_path, vec![4, 5, 6]);
+
+    // offloaded values are read when their entry is replayed
+    let load = || {
+        let _guard = initialize_tv_case_from_file(
+            &tv_file_path,
+            TestVectorFileFormat::Json,
+            TestMode::Check,
+        )?;
+        let fields = TestVectorActive::initialize_values::<OffloadedFields>();
+        process_next_value(TestVectorEntryType::Const, &fields.first, &vec![], None)?;
+        process_next_value(TestVectorEntryType::Const, &fields.second, &vec![], None)?;
+        finalize_tv_case()
+    };
+    let stored = std::fs::read_to_string(&tv_file_path).unwrap();
+    let entries: serde_json::Value = serde_json::from_str(&stored).unwrap();
+    let hash = entries["entries"][0]["offload_hash"].as_str().unwrap();
+    let blob = dir.join("blobs").join(format!("{hash}.bin.zst"));
+    let other_hash =
+        serde_json::from_slice::<serde_json::Value>(&std::fs::read(&other_file_path).unwrap())
+            .unwrap()["entries"][0]["offload_hash"]
+            .as_str()
+            .unwrap()
+            .to_string();
+
+    // a recorded length that does not match the blob
+    std::fs::write(
+        &tv_file_path,
+        stored.replace("\"offload_len\": 3", "\"offload_len\": 2"),
+    )
+    .unwrap();
+    match load().unwrap_err() {
+        TestVectorError::OffloadIntegrity {
+            entry_index,
+            expected,
+            actual,
+            ..
+        } => assert_eq!(
+            (entry_index, expected.len, actual.len),
+            (0, Some(2), Some(3))
+        ),
+        err => panic!("expected an integrity error, got: {err}"),
+    }
+    std::fs::write(&tv_file_path, &stored).unwrap();
+    load().unwrap();
+
+    // a blob swapped with the blob of another value
+    std::fs::copy(
+        dir.join("blobs").join(format!("{other_hash}.bin.zst")),
+        &blob,
+    )
+    .unwrap();
+    let err = load().unwrap_err();
+    assert!(
+        matches!(&err, TestVectorError::OffloadIntegrity { path, actual, .. } if *path == blob && actual.hash == other_hash),
+        "unexpected error: {err}"
+    );
+    assert!(err.to_string().contains(hash), "{err}");
+
+    // re-recording replaces a swapped or truncated blob
+    offload(&tv_file_path, vec![1, 2, 3]);
+    load().unwrap();
+    let intact = std::fs::read(&blob).unwrap();
+    std::fs::write(&blob, &intact[..intact.len() / 2]).unwrap();
+    assert!(load().is_err());
+    offload(&tv_file_path, vec![1, 2, 3]);
+    assert_eq!(std::fs::read(&blob).unwrap(), intact);
+    load().unwrap();
+}
+
+#[derive(TestVectorSet)]
+struct CodecFields {
+    #[test_vec(name = "session", offload = true)]
+    session: TestValue<Vec<u64>>,
+    #[test_vec(name = "plain", offload = true, offload_codec = "none")]
+    plain: TestValue<Vec<u64>>,
+}
+
+#[test]
+fn test_offload_codecs() {
+    let dir = TempDir::new("codec");
+    let tv_file_path = dir.join("case.json");
+    let session_codecs = [
+        OffloadCodec::Zstd { level: 1 },
+        #[cfg(feature = "gzip")]
+        OffloadCodec::Gzip { level: 1 },
+    ];
+    for session_codec in session_codecs {
+        let guard = initialize_tv_case_with_options(
+            &tv_file_path,
+            TestVectorFileFormat::Json,
+            TestMode::Init,
+            TestVectorOptions {
+                offload_codec: session_codec,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let fields = TestVectorActive::initialize_values::<CodecFields>();
+        TestVectorActive::expose_value(&fields.session, vec![1, 2]);
+        TestVectorActive::expose_value(&fields.plain, vec![300, 400]);
+        finalize_tv_case().unwrap();
+        drop(guard);
+
+        let stored: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&tv_file_path).unwrap()).unwrap();
+        let blob = |index: usize| {
+            let codec: OffloadCodec =
+                serde_json::from_value(stored["entries"][index]["offload_codec"].clone()).unwrap();
+            let hash = stored["entries"][index]["offload_hash"].as_str().unwrap();
+            (codec, hash.to_string())
+        };
+        assert_eq!(blob(0).0, session_codec);
+        assert_eq!(blob(1).0, OffloadCodec::None);
+        let plain_blob = dir.join("blobs").join(format!("{}.json", blob(1).1));
+        assert_eq!(std::fs::read_to_string(plain_blob).unwrap(), "[300,400]");
+
+        // the recorded codec is used when loading, independent of the session default
+        let replayed = run(&tv_file_path, TestMode::Check, |fields: CodecFields| {
+            (
+                TestVectorActive::expose_value(&fields.session, vec![]),
+                TestVectorActive::expose_value(&fields.plain, vec![]),
+            )
+        });
+        assert_eq!(replayed, (vec![1, 2], vec![300, 400]));
+    }
+}
+
+#[derive(TestVectorSet)]
+struct ByteFields {
+    #[test_vec(name = "image", bytes, offload_codec = "none")]
+    image: TestValue<Vec<u8>>,
+    #[test_vec(name = "packet", bytes)]
+    packet: TestValue<Vec<u8>>,
+    #[test_vec(name = "frame", offload = true)]
+    frame: TestValue<Vec<u8>>,
+    #[test_vec(name = "samples", offload = true)]
+    samples: TestValue<Vec<i32>>,
+}
+
+#[test]
+fn test_offloaded_bytes_are_stored_raw() {
+    let dir = TempDir::new("bytes");
+    let tv_file_path = dir.join("case.json");
+    let image: Vec<u8> = (0..=255).cycle().take(1000).collect();
+    run(&tv_file_path, TestMode::Init, |fields: ByteFields| {
+        TestVectorActive::expose_value(&fields.image, image.clone());
+        TestVectorActive::expose_value(&fields.packet, vec![0; 4096]);
+        TestVectorActive::expose_value(&fields.frame, vec![7; 64]);
+        TestVectorActive::expose_value(&fields.samples, vec![-1, 256]);
+    });
+
+    let stored: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(&tv_file_path).unwrap()).unwrap();
+    let hash = |index: usize| stored["entries"][index]["offload_hash"].as_str().unwrap();
+    assert_eq!(stored["entries"][0]["offload_encoding"], "bytes");
+    assert_eq!(
+        std::fs::read(dir.join("blobs").join(format!("{}.bin", hash(0)))).unwrap(),
+        image
+    );
+    assert_eq!(stored["entries"][1]["offload_len"], 4096);
+    assert!(dir
+        .join("blobs")
+        .join(format!("{}.bin.zst", hash(1)))
+        .exists());
+    // offloaded byte arrays are detected without the attribute
+    assert_eq!(stored["entries"][2]["offload_encoding"], "bytes");
+    assert!(stored["entries"][3].get("offload_encoding").is_none());
+
+    let replayed = run(&tv_file_path, TestMode::Check, |fields: ByteFields| {
+        (
+            TestVectorActive::expose_value(&fields.image, vec![]),
+            TestVectorActive::expose_value(&fields.packet, vec![]),
+            TestVectorActive::expose_value(&fields.frame, vec![]),
+            TestVectorActive::expose_value(&fields.samples, vec![]),
+        )
+    });
+    assert_eq!(replayed, (image, vec![0; 4096], vec![7; 64], vec![-1, 256]));
+}
+
+#[derive(TestVectorSet)]
+struct EncodedFields {
+    #[test_vec(name = "key", encoding = "hex")]
+    key: TestValue<[u8; 32]>,
+    #[test_vec(name = "ciphertext", encoding = "base64")]
+    ciphertext: TestValue<Vec<u8>>,
+}
+
+#[test]
+fn test_encoded_byte_fields() {
+    let (_dir, tv_file_path) = tv_file("encoded");
+    let key: [u8; 32] = std::array::from_fn(|i| i as u8 * 8);
+    let ciphertext = b"attack at dawn".to_vec();
+    run(&tv_file_path, TestMode::Init, |fields: EncodedFields| {
+        TestVectorActive::expose_value(&fields.key, key);
+        TestVectorActive::check_value(&fields.ciphertext, &ciphertext);
+    });
+
+    let stored: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(&tv_file_path).unwrap()).unwrap();
+    assert_eq!(
+        stored["entries"][0]["value"],
+        "0008101820283038404850586068707880889098a0a8b0b8c0c8d0d8e0e8f0f8"
+    );
+    assert_eq!(stored["entries"][1]["value"], "YXR0YWNrIGF0IGRhd24=");
+
+    let replayed = run(&tv_file_path, TestMode::Check, |fields: EncodedFields| {
+        let replayed = TestVectorActive::expose_value(&fields.key, [0; 32]);
+        TestVectorActive::check_value(&fields.ciphertext, &ciphertext);
+        replayed
+    });
+    assert_eq!(replayed, key);
+}
+
+#[derive(TestVectorSet)]
+struct FormatFields {
+    #[test_vec(name = "samples")]
+    samples: TestValue<Vec<u8>>,
+    #[test_vec(name = "stats", offload = true)]
+    stats: TestValue<(f64, i64, Option<String>)>,
+}
+
+fn record_in_format(tv_file_path: &Path, format: TestVectorFileFormat, mode: TestMode) {
+    let guard = initialize_tv_case_from_file(tv_file_path, format, mode).unwrap();
+    let fields = TestVectorActive::initialize_values::<FormatFields>();
+    TestVectorActive::check_value(&fields.samples, &vec![0, 127, 255]);
+    TestVectorActive::check_value(&fields.stats, &(0.1, -7, None));
+    finalize_tv_case().unwrap();
+    drop(guard);
+}
+
+#[test]
+fn test_file_formats() {
+    let dir = TempDir::new("formats");
+    let formats: &[(TestVectorFileFormat, &str)] = &[
+        (TestVectorFileFormat::JsonLines, "jsonl"),
+        #[cfg(feature = "ron")]
+        (TestVectorFileFormat::Ron, "ron"),
+        #[cfg(feature = "cbor")]
+        (TestVectorFileFormat::Cbor, "cbor"),
+        #[cfg(feature = "msgpack")]
+        (TestVectorFileFormat::MessagePack, "msgpack"),
+    ];
+    for &(format, extension) in formats {
+        let tv_file_path = dir.join(format!("case.{extension}"));
+        record_in_format(&tv_file_path, format, TestMode::Init);
+        record_in_format(&tv_file_path, format, TestMode::Check);
+        let stored = std::fs::read(&tv_file_path).unwrap();
+        assert!(stored.windows(7).any(|window| window == b"samples"));
+    }
+
+    // a header line, then one self-contained JSON entry per line
+    let stored = std::fs::read_to_string(dir.join("case.jsonl")).unwrap();
+    let lines: Vec<serde_json::Value> = stored
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0]["header"]["schema_version"], 1);
+    assert_eq!(lines[1]["value"], serde_json::json!([0, 127, 255]));
+}
+
+#[cfg(not(feature = "cbor"))]
+#[test]
+fn test_binary_format_requires_feature() {
+    let (_dir, tv_file_path) = tv_file("cbor_disabled");
+    let tv_file_path = tv_file_path.with_extension("cbor");
+    // rejected when the session starts, before the code under test runs
+    let err =
+        initialize_tv_case_from_file(&tv_file_path, TestVectorFileFormat::Cbor, TestMode::Init)
+            .err()
+            .expect("the `cbor` feature is disabled");
+    assert!(
+        matches!(
+            &err,
+            TestVectorError::Encode {
+                format: TestVectorFileFormat::Cbor,
+                ..
+            }
+        ),
+        "unexpected error: {err}"
+    );
+    assert!(err.to_string().contains("`cbor` feature"), "{err}");
+    assert!(!tv_file_path.exists());
+}
+
+#[test]
+fn test_format_from_path() {
+    assert_eq!(
+        TestVectorFileFormat::from_path("vecs/case.yml"),
+        Some(TestVectorFileFormat::Yaml)
+    );
+    assert_eq!(
+        TestVectorFileFormat::from_path("case.jsonl"),
+        Some(TestVectorFileFormat::JsonLines)
+    );
+    assert_eq!(TestVectorFileFormat::from_path("case.txt"), None);
+    assert_eq!(TestVectorFileFormat::from_path("case"), None);
+
+    let dir = TempDir::new("auto");
+    let tv_file_path = dir.join("case.yaml");
+    let record = |mode| {
+        let guard =
+            initialize_tv_case_from_file(&tv_file_path, TestVectorFileFormat::Auto, mode).unwrap();
+        let fields = TestVectorActive::initialize_values::<FormatFields>();
+        TestVectorActive::check_value(&fields.samples, &vec![1, 2, 3]);
+        TestVectorActive::check_value(&fields.stats, &(0.5, 1, Some("yaml".to_string())));
+        finalize_tv_case().unwrap();
+        drop(guard);
+    };
+    record(TestMode::Init);
+    record(TestMode::Check);
+    let stored = std::fs::read_to_string(&tv_file_path).unwrap();
+    assert!(stored.starts_with("header:"), "{stored}");
+
+    let unknown = dir.join("case.txt");
+    match initialize_tv_case_from_file(&unknown, TestVectorFileFormat::Auto, TestMode::Init) {
+        Err(TestVectorError::UnknownFormat { path }) => assert_eq!(path, unknown),
+        Err(err) => panic!("expected an unknown format error, got: {err}"),
+        Ok(_) => panic!("expected an unknown format error"),
+    }
+}
+
+#[test]
+fn test_offloaded_values_are_loaded_lazily() {
+    let dir = TempDir::new("lazy");
+    let tv_file_path = dir.join("case.json");
+    run(&tv_file_path, TestMode::Init, |fields: OffloadedFields| {
+        TestVectorActive::expose_value(&fields.first, vec![1]);
+        TestVectorActive::expose_value(&fields.second, vec![2]);
+    });
+    let stored: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(&tv_file_path).unwrap()).unwrap();
+    let second_hash = stored["entries"][1]["offload_hash"].as_str().unwrap();
+    let second_blob = dir.join("blobs").join(format!("{second_hash}.bin.zst"));
+    std::fs::remove_file(&second_blob).unwrap();
+
+    // a session that stops before the second entry never reads its blob
+    let guard = initialize_tv_case_with_options(
+        &tv_file_path,
+        TestVectorFileFormat::Json,
+        TestMode::Check,
+        TestVectorOptions {
+            allow_unconsumed: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let fields = TestVectorActive::initialize_values::<OffloadedFields>();
+    assert_eq!(
+        TestVectorActive::expose_value(&fields.first, vec![]),
+        vec![1]
+    );
+    match process_next_value(TestVectorEntryType::Const, &fields.second, &vec![], None) {
+        Err(TestVectorError::OffloadMissing {
+            entry_index, path, ..
+        }) => assert_eq!((entry_index, path), (1, second_blob)),
+        other => panic!("expected a missing offload, got: {other:?}"),
+    }
+    finalize_tv_case().unwrap();
+    drop(guard);
+}
+
+#[derive(TestVectorSet)]
+struct HeaderFields {
+    #[test_vec(name = "answer")]
+    answer: TestValue<u64>,
+}
+
+#[test]
+fn test_file_header() {
+    let (_dir, tv_file_path) = tv_file("header");
+    let session = |mode, metadata: &[(&str, &str)]| {
+        let guard = initialize_tv_case_with_options(
+            &tv_file_path,
+            TestVectorFileFormat::Json,
+            mode,
+            TestVectorOptions {
+                metadata: metadata
+                    .iter()
+                    .map(|(key, value)| (key.to_string(), value.to_string()))
+                    .collect(),
+                package_name: Some(env!("CARGO_PKG_NAME").to_string()),
+                package_version: Some(env!("CARGO_PKG_VERSION").to_string()),
+                ..Default::default()
+            },
+        )?;
+        let fields = TestVectorActive::initialize_values::<HeaderFields>();
+        TestVectorActive::check_value(&fields.answer, &42);
+        let finalized = finalize_tv_case();
+        drop(guard);
+        finalized
+    };
+    let stored = || -> serde_json::Value {
+        serde_json::from_slice(&std::fs::read(&tv_file_path).unwrap()).unwrap()
+    };
+
+    session(TestMode::Init, &[("scenario", "baseline")]).unwrap();
+    let header = stored()["header"].clone();
+    assert_eq!(header["schema_version"], 1);
+    assert_eq!(header["generator_version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(header["package_name"], "assert_tv");
+    assert_eq!(header["package_version"], env!("CARGO_PKG_VERSION"));
+    assert!(header["target"].is_string(), "{header}");
+    assert_eq!(header["metadata"]["scenario"], "baseline");
+
+    // only changed entries rewrite the file in update mode
+    session(TestMode::Update, &[("scenario", "other")]).unwrap();
+    assert_eq!(stored()["header"]["metadata"]["scenario"], "baseline");
+
+    // files without a header still load
+    let mut without_header = stored();
+    without_header.as_object_mut().unwrap().remove("header");
+    std::fs::write(&tv_file_path, without_header.to_string()).unwrap();
+    session(TestMode::Check, &[]).unwrap();
+
+    // files of a newer schema are rejected
+    let mut newer = stored();
+    newer["header"] = header;
+    newer["header"]["schema_version"] = 99.into();
+    std::fs::write(&tv_file_path, newer.to_string()).unwrap();
+    match session(TestMode::Check, &[]) {
+        Err(TestVectorError::UnsupportedSchema { schema_version, .. }) => {
+            assert_eq!(schema_version, 99)
+        }
+        other => panic!("expected an unsupported schema, got: {other:?}"),
+    }
+
+    // malformed headers are rejected
+    let mut malformed = newer;
+    malformed["header"]["schema_version"] = 1.into();
+    malformed["header"]["generator_version"] = "latest".into();
+    std::fs::write(&tv_file_path, malformed.to_string()).unwrap();
+    match session(TestMode::Check, &[]) {
+        Err(TestVectorError::Parse { message, .. }) => {
+            assert!(message.contains("`generator_version`"), "{message}")
+        }
+        other => panic!("expected an invalid header, got: {other:?}"),
+    }
+
+    // the package is not read from the environment of the test process
+    let guard =
+        initialize_tv_case_from_file(&tv_file_path, TestVectorFileFormat::Json, TestMode::Init)
+            .unwrap();
+    let fields = TestVectorActive::initialize_values::<HeaderFields>();
+    TestVectorActive::check_value(&fields.answer, &42);
+    finalize_tv_case().unwrap();
+    drop(guard);
+    let header = stored()["header"].clone();
+    assert!(header.get("package_name").is_none(), "{header}");
+}
@@ -0,0 +1,197 @@
+mod common;
+
+use assert_tv::{
+    finalize_tv_case, initialize_tv_case_with_options, process_next_value, MatchingStrategy,
+    TestMode, TestValue, TestVector, TestVectorActive, TestVectorEntryType, TestVectorError,
+    TestVectorFileFormat, TestVectorOptions, TestVectorSet,
+};
+use common::TempDir;
+use std::path::{Path, PathBuf};
+
+#[derive(TestVectorSet)]
+struct StepFields {
+    #[test_vec(name = "seed")]
+    seed: TestValue<u64>,
+    #[test_vec(name = "state", offload_threshold = 64)]
+    state: TestValue<Vec<u64>>,
+}
+
+fn streaming() -> TestVectorOptions {
+    TestVectorOptions {
+        streaming: true,
+        ..Default::default()
+    }
+}
+
+fn partial_path(tv_file_path: &Path) -> PathBuf {
+    tv_file_path.with_extension("jsonl.partial")
+}
+
+/// Runs `steps` simulation steps, each exposing a seed and checking the resulting state.
+fn simulate(steps: u64, drift: u64) -> u64 {
+    let mut total = 0;
+    for step in 0..steps {
+        let fields = TestVectorActive::initialize_values::<StepFields>();
+        let seed = TestVectorActive::expose_value(&fields.seed, step);
+        let state: Vec<u64> = (0..seed % 20).map(|i| i * seed + drift).collect();
+        TestVectorActive::check_value(&fields.state, &state);
+        total += seed;
+    }
+    total
+}
+
+#[test]
+fn test_streaming_round_trip() {
+    let dir = TempDir::new("streaming");
+    let tv_file_path = dir.join("sim.jsonl");
+    let session = |mode| {
+        initialize_tv_case_with_options(
+            &tv_file_path,
+            TestVectorFileFormat::JsonLines,
+            mode,
+            streaming(),
+        )
+        .unwrap()
+    };
+
+    // entries are on disk before the session is finalized
+    let guard = session(TestMode::Init);
+    simulate(100, 0);
+    let partial = std::fs::read_to_string(partial_path(&tv_file_path)).unwrap();
+    // the header line and two entries per step
+    assert_eq!(partial.lines().count(), 201);
+    assert!(!tv_file_path.exists());
+    finalize_tv_case().unwrap();
+    drop(guard);
+    assert!(!partial_path(&tv_file_path).exists());
+    let stored = std::fs::read_to_string(&tv_file_path).unwrap();
+    assert_eq!(stored.lines().count(), 201);
+    assert!(stored.contains("offload_hash"));
+
+    let guard = session(TestMode::Check);
+    assert_eq!(simulate(100, 0), 4950);
+    finalize_tv_case().unwrap();
+    drop(guard);
+
+    // mismatches are detected on the streamed entries
+    let guard = session(TestMode::Check);
+    let drifted = std::panic::catch_unwind(|| simulate(100, 1));
+    drop(guard);
+    assert!(drifted.is_err());
+
+    // entries that were never read are reported
+    let guard = session(TestMode::Check);
+    simulate(98, 0);
+    match finalize_tv_case().unwrap_err() {
+        TestVectorError::UnconsumedEntries {
+            recorded,
+            loaded,
+            entries,
+        } => {
+            assert_eq!((recorded, loaded), (196, 200));
+            let indices: Vec<usize> = entries.iter().map(|entry| entry.index).collect();
+            assert_eq!(indices, vec![196, 197, 198, 199]);
+        }
+        err => panic!("expected unconsumed entries, got: {err}"),
+    }
+    drop(guard);
+}
+
+#[test]
+fn test_streaming_requires_json_lines_and_positional_matching() {
+    let unsupported = |tv_file_path: &str, file_format, test_mode, options| {
+        match initialize_tv_case_with_options(tv_file_path, file_format, test_mode, options) {
+            Err(TestVectorError::StreamingUnsupported { .. }) => {}
+            Err(err) => panic!("expected a streaming error, got: {err}"),
+            Ok(_) => panic!("expected a streaming error"),
+        }
+    };
+    unsupported(
+        "case.json",
+        TestVectorFileFormat::Json,
+        TestMode::Init,
+        streaming(),
+    );
+    unsupported(
+        "case.jsonl",
+        TestVectorFileFormat::JsonLines,
+        TestMode::Init,
+        TestVectorOptions {
+            matching: MatchingStrategy::Keyed,
+            ..streaming()
+        },
+    );
+    unsupported(
+        "case.jsonl",
+        TestVectorFileFormat::JsonLines,
+        TestMode::Update,
+        streaming(),
+    );
+}
+
+#[test]
+fn test_header_line_is_recognized_by_its_key() {
+    let dir = TempDir::new("header_line");
+    let tv_file_path = dir.join("sim.jsonl");
+    let guard = initialize_tv_case_with_options(
+        &tv_file_path,
+        TestVectorFileFormat::JsonLines,
+        TestMode::Init,
+        streaming(),
+    )
+    .unwrap();
+    simulate(1, 0);
+    finalize_tv_case().unwrap();
+    drop(guard);
+    let stored = std::fs::read_to_string(&tv_file_path).unwrap();
+    assert!(
+        stored.starts_with("{\"header\":{\"schema_version\":1,"),
+        "{stored}"
+    );
+
+    // the header is read when the session starts, or with the first entry when streaming
+    let load = |options: TestVectorOptions| {
+        let _guard = initialize_tv_case_with_options(
+            &tv_file_path,
+            TestVectorFileFormat::JsonLines,
+            TestMode::Check,
+            options,
+        )?;
+        let fields = TestVectorActive::initialize_values::<StepFields>();
+        process_next_value(TestVectorEntryType::Const, &fields.seed, &0, None).map(|_| ())
+    };
+    for options in [TestVectorOptions::default(), streaming()] {
+        std::fs::write(
+            &tv_file_path,
+            stored.replacen("\"schema_version\":1", "\"schema_version\":\"1\"", 1),
+        )
+        .unwrap();
+        match load(options.clone()).unwrap_err() {
+            TestVectorError::Parse { message, .. } => {
+                assert!(message.starts_with("line 1: invalid header"), "{message}")
+            }
+            err => panic!("expected a header parse error, got: {err}"),
+        }
+        std::fs::write(
+            &tv_file_path,
+            stored.replacen("\"schema_version\":1", "\"schema_version\":99", 1),
+        )
+        .unwrap();
+        match load(options.clone()).unwrap_err() {
+            TestVectorError::UnsupportedSchema { schema_version, .. } => {
+                assert_eq!(schema_version, 99)
+            }
+            err => panic!("expected an unsupported schema, got: {err}"),
+        }
+        // only the first line may hold a header
+        let header_line = stored.lines().next().unwrap();
+        std::fs::write(&tv_file_path, format!("{header_line}\n{stored}")).unwrap();
+        match load(options).unwrap_err() {
+            TestVectorError::Parse { message, .. } => assert_eq!(
+                message,
+                "line 2: a header is only allowed on the first line"
+            ),
+            err => panic!("expected a misplaced header error, got: {err}"),
+        }
+    }
+}
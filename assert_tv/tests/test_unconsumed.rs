@@ -0,0 +1,87 @@
+mod common;
+
+use assert_tv::{
+    finalize_tv_case, initialize_tv_case_from_file, initialize_tv_case_with_options, TestMode,
+    TestValue, TestVector, TestVectorActive, TestVectorEntryType, TestVectorError,
+    TestVectorFileFormat, TestVectorOptions, TestVectorSet,
+};
+use common::tv_file;
+
+#[derive(TestVectorSet)]
+struct Fields {
+    #[test_vec(name = "x")]
+    x: TestValue<u64>,
+    #[test_vec(name = "y")]
+    y: TestValue<u64>,
+}
+
+fn computation<TV: TestVector>(stop_early: bool) {
+    let fields = TV::initialize_values::<Fields>();
+    let x = TV::expose_value(&fields.x, 20);
+    if stop_early {
+        return;
+    }
+    TV::check_value(&fields.y, &(x + 1));
+}
+
+#[test]
+fn test_unconsumed_entries_fail_check() {
+    let (_dir, tv_file_path) = tv_file("unconsumed");
+    let guard =
+        initialize_tv_case_from_file(&tv_file_path, TestVectorFileFormat::Json, TestMode::Init)
+            .unwrap();
+    computation::<TestVectorActive>(false);
+    finalize_tv_case().unwrap();
+    drop(guard);
+
+    // replaying everything passes
+    let guard =
+        initialize_tv_case_from_file(&tv_file_path, TestVectorFileFormat::Json, TestMode::Check)
+            .unwrap();
+    computation::<TestVectorActive>(false);
+    finalize_tv_case().unwrap();
+    drop(guard);
+
+    // stopping early reports the remaining entry
+    let guard =
+        initialize_tv_case_from_file(&tv_file_path, TestVectorFileFormat::Json, TestMode::Check)
+            .unwrap();
+    computation::<TestVectorActive>(true);
+    let err = finalize_tv_case().expect_err("unconsumed entries must fail the check");
+    assert!(
+        err.to_string()
+            .starts_with("1 of 2 loaded entries were never replayed:"),
+        "{err}"
+    );
+    let TestVectorError::UnconsumedEntries {
+        recorded,
+        loaded,
+        entries,
+    } = err
+    else {
+        panic!("expected unconsumed entries error, got: {err}");
+    };
+    assert_eq!(recorded, 1);
+    assert_eq!(loaded, 2);
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].index, 1);
+    assert_eq!(entries[0].name.as_deref(), Some("y"));
+    assert_eq!(entries[0].entry_type, TestVectorEntryType::Output);
+    assert!(entries[0].code_location.is_some());
+    drop(guard);
+
+    // partial replays can opt out
+    let guard = initialize_tv_case_with_options(
+        &tv_file_path,
+        TestVectorFileFormat::Json,
+        TestMode::Check,
+        TestVectorOptions {
+            allow_unconsumed: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    computation::<TestVectorActive>(true);
+    finalize_tv_case().unwrap();
+    drop(guard);
+}
@@ -0,0 +1,108 @@
+mod common;
+
+use assert_tv::{
+    finalize_tv_case, initialize_tv_case_from_file, TestMode, TestValue, TestVector,
+    TestVectorActive, TestVectorFileFormat, TestVectorSet,
+};
+use common::{tv_file, TempDir};
+use std::path::Path;
+
+#[derive(TestVectorSet)]
+struct Fields {
+    #[test_vec(name = "first")]
+    first: TestValue<u64>,
+    #[test_vec(name = "second")]
+    second: TestValue<u64>,
+    #[test_vec(name = "sum")]
+    sum: TestValue<u64>,
+    #[test_vec(name = "product")]
+    product: TestValue<u64>,
+}
+
+/// `version` models an intentional behaviour change of the code under test.
+fn computation<TV: TestVector>(first: u64, second: u64, version: u64) -> (u64, u64) {
+    let fields = TV::initialize_values::<Fields>();
+    let first = TV::expose_value(&fields.first, first);
+    if version > 1 {
+        TV::check_value(&fields.product, &(first * version));
+    }
+    let second = TV::expose_value(&fields.second, second);
+    TV::check_value(&fields.sum, &(first + second * version));
+    (first, second)
+}
+
+fn run(tv_file_path: &Path, mode: TestMode, inputs: (u64, u64), version: u64) -> (u64, u64) {
+    let guard =
+        initialize_tv_case_from_file(tv_file_path, TestVectorFileFormat::Json, mode).unwrap();
+    let consts = computation::<TestVectorActive>(inputs.0, inputs.1, version);
+    finalize_tv_case().unwrap();
+    drop(guard);
+    consts
+}
+
+#[test]
+fn test_update_keeps_consts_and_rewrites_outputs() {
+    let (_dir, tv_file_path) = tv_file("update");
+    assert_eq!(run(&tv_file_path, TestMode::Init, (3, 4), 1), (3, 4));
+
+    // update replays the stored consts and records the outputs of the new behaviour
+    assert_eq!(run(&tv_file_path, TestMode::Update, (30, 40), 2), (3, 4));
+
+    // the rewritten file passes check mode with the new behaviour and the original inputs
+    assert_eq!(run(&tv_file_path, TestMode::Check, (300, 400), 2), (3, 4));
+    let guard =
+        initialize_tv_case_from_file(&tv_file_path, TestVectorFileFormat::Json, TestMode::Check)
+            .unwrap();
+    let old_behaviour = std::panic::catch_unwind(|| computation::<TestVectorActive>(0, 0, 1));
+    assert!(old_behaviour.is_err());
+    drop(guard);
+}
+
+#[derive(TestVectorSet)]
+struct OffloadedFields {
+    #[test_vec(name = "input", offload = true)]
+    input: TestValue<Vec<u64>>,
+    #[test_vec(name = "output", offload = true)]
+    output: TestValue<Vec<u64>>,
+}
+
+fn run_offloaded(tv_file_path: &Path, mode: TestMode, input: Vec<u64>) -> Vec<u64> {
+    let guard =
+        initialize_tv_case_from_file(tv_file_path, TestVectorFileFormat::Json, mode).unwrap();
+    let fields = TestVectorActive::initialize_values::<OffloadedFields>();
+    let input = TestVectorActive::expose_value(&fields.input, input);
+    TestVectorActive::check_value(&fields.output, &input.iter().map(|x| x * 2).collect());
+    finalize_tv_case().unwrap();
+    drop(guard);
+    input
+}
+
+#[test]
+fn test_update_rewrites_undecodable_outputs() {
+    let dir = TempDir::new("update_blob");
+    let tv_file_path = dir.join("case.json");
+    run_offloaded(&tv_file_path, TestMode::Init, vec![1, 2, 3]);
+    let stored: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&tv_file_path).unwrap()).unwrap();
+    let output_hash = stored["entries"][1]["offload_hash"].as_str().unwrap();
+    let output_blob = std::fs::read_dir(dir.join("blobs"))
+        .unwrap()
+        .map(|blob| blob.unwrap().path())
+        .find(|blob| {
+            blob.file_name()
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .starts_with(output_hash)
+        })
+        .unwrap();
+    std::fs::remove_file(&output_blob).unwrap();
+
+    // the output is unchanged, but its blob is missing, so update records it again
+    assert_eq!(
+        run_offloaded(&tv_file_path, TestMode::Update, vec![4, 5]),
+        vec![1, 2, 3]
+    );
+    assert!(output_blob.exists());
+    run_offloaded(&tv_file_path, TestMode::Check, vec![]);
+}
@@ -1,7 +1,10 @@
 //! Derive macro for `assert_tv::TestVectorSet`.
 use proc_macro2::Span;
 use quote::quote;
-use syn::{spanned::Spanned, Attribute, Data, DeriveInput, Error, Fields, LitBool, LitStr, Type};
+use syn::{
+    spanned::Spanned, Attribute, Data, DeriveInput, Error, Expr, ExprLit, Fields, Lit, LitBool,
+    LitInt, LitStr, Token, Type,
+};
 // -----------------------------------------------------------------------------
 // Implementation
 // -----------------------------------------------------------------------------
@@ -15,8 +18,18 @@ struct FieldCfg {
     description: Option<String>,
     serialize_with: Option<syn::Path>,
     deserialize_with: Option<syn::Path>,
+    encoding: Option<syn::Ident>,
+    compare_with: Option<syn::Path>,
     compress: Option<bool>,
     offload: Option<bool>,
+    offload_threshold: Option<usize>,
+    offload_codec: Option<proc_macro2::TokenStream>,
+    bytes: Option<bool>,
+    tolerance: Option<f64>,
+    rel_tolerance: Option<f64>,
+    unordered: Option<Vec<String>>,
+    ignore_paths: Option<Vec<String>>,
+    redact: Option<String>,
     span: Span,
 }
 
@@ -56,40 +69,79 @@ pub(crate) fn expand(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStrea
         // ensure field type is `TestValue<..>`
         ensure_test_value_type(&field.ty)?;
 
-        let mut name = None;
-        let mut description = None;
-        let mut serialize_with = None;
-        let mut deserialize_with = None;
-        let mut compress = None;
-        let mut offload = None;
+        let mut cfg = FieldCfg {
+            ident,
+            ty: field.ty.clone(),
+            name: None,
+            description: None,
+            serialize_with: None,
+            deserialize_with: None,
+            encoding: None,
+            compare_with: None,
+            compress: None,
+            offload: None,
+            offload_threshold: None,
+            offload_codec: None,
+            bytes: None,
+            tolerance: None,
+            rel_tolerance: None,
+            unordered: None,
+            ignore_paths: None,
+            redact: None,
+            span,
+        };
 
         for attr in &field.attrs {
             if !attr.path().is_ident("test_vec") {
                 continue;
             }
 
-            parse_test_vec_attribute(
-                attr,
-                &mut name,
-                &mut description,
-                &mut serialize_with,
-                &mut deserialize_with,
-                &mut compress,
-                &mut offload,
-            )?;
+            parse_test_vec_attribute(attr, &mut cfg)?;
         }
 
-        cfgs.push(FieldCfg {
-            ident,
-            ty: field.ty.clone(),
-            name,
-            description,
-            serialize_with,
-            deserialize_with,
-            compress,
-            offload,
-            span,
-        });
+        if let Some(encoding) = &cfg.encoding {
+            if cfg.serialize_with.is_some() || cfg.deserialize_with.is_some() {
+                return Err(Error::new_spanned(
+                    encoding,
+                    "`encoding` cannot be combined with `serialize_with` or `deserialize_with`",
+                ));
+            }
+            if cfg.bytes == Some(true) {
+                return Err(Error::new_spanned(
+                    encoding,
+                    "`encoding` stores a string and cannot be combined with `bytes`",
+                ));
+            }
+        }
+
+        if let Some(compare_with) = &cfg.compare_with {
+            let conflicting_key = if cfg.tolerance.is_some() {
+                Some("tolerance")
+            } else if cfg.rel_tolerance.is_some() {
+                Some("rel_tolerance")
+            } else if cfg.unordered.is_some() {
+                Some("unordered")
+            } else if cfg
+                .ignore_paths
+                .as_ref()
+                .is_some_and(|paths| !paths.is_empty())
+            {
+                Some("ignore_paths")
+            } else {
+                None
+            };
+            if let Some(key) = conflicting_key {
+                return Err(Error::new_spanned(
+                    compare_with,
+                    format!(
+                        "`compare_with` replaces the structural comparison and cannot be combined with `{}`",
+                        key
+                    ),
+                ));
+            }
+        }
+
+        cfgs.push(cfg);
     }
 
     // 3. Generate the body of `Self { ... }`
@@ -99,6 +151,25 @@ pub(crate) fn expand(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStrea
         let description = opt_string(&f.description);
         let compress = opt_bool_default_false(&f.compress);
         let offload = opt_bool_default_false(&f.offload);
+        let offload_threshold = match &f.offload_threshold {
+            Some(v) => quote! { Some(#v) },
+            None => quote! { None },
+        };
+        let offload_codec = match &f.offload_codec {
+            Some(codec) => quote! { Some(#codec) },
+            None => quote! { None },
+        };
+        let bytes = opt_bool_default_false(&f.bytes);
+        let tolerance = opt_f64(&f.tolerance);
+        let rel_tolerance = opt_f64(&f.rel_tolerance);
+        let unordered = match &f.unordered {
+            Some(paths) => {
+                quote! { Some(::std::vec![#(::std::string::String::from(#paths)),*]) }
+            }
+            None => quote! { None },
+        };
+        let ignore_paths = f.ignore_paths.iter().flatten();
+        let redact = opt_string(&f.redact);
 
         let serializer = if let Some(path) = &f.serialize_with {
             quote! {
@@ -108,6 +179,14 @@ pub(crate) fn expand(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStrea
                     None
                 }
             }
+        } else if let Some(encoding) = &f.encoding {
+            quote! {
+                if TV::is_test_vector_enabled() {
+                    Some(::std::boxed::Box::new(|v| ::assert_tv::encoding::#encoding::serialize(v)))
+                } else {
+                    None
+                }
+            }
         } else {
             // default serde_json serializer
             quote! {
@@ -127,6 +206,14 @@ pub(crate) fn expand(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStrea
                     None
                 }
             }
+        } else if let Some(encoding) = &f.encoding {
+            quote! {
+                if TV::is_test_vector_enabled() {
+                    Some(::std::boxed::Box::new(|v| ::assert_tv::encoding::#encoding::deserialize(v)))
+                } else {
+                    None
+                }
+            }
         } else {
             quote! {
                 if TV::is_test_vector_enabled() {
@@ -137,6 +224,18 @@ pub(crate) fn expand(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStrea
             }
         };
 
+        let comparator = if let Some(path) = &f.compare_with {
+            quote! {
+                if TV::is_test_vector_enabled() {
+                    Some(::std::boxed::Box::new(#path))
+                } else {
+                    None
+                }
+            }
+        } else {
+            quote! { None }
+        };
+
         quote! {
             #ident: ::assert_tv::TestValue {
                 name: #name,
@@ -146,6 +245,15 @@ pub(crate) fn expand(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStrea
                 deserializer: #deserializer,
                 compress: #compress,
                 offload: #offload,
+                offload_threshold: #offload_threshold,
+                offload_codec: #offload_codec,
+                bytes: #bytes,
+                tolerance: #tolerance,
+                rel_tolerance: #rel_tolerance,
+                comparator: #comparator,
+                unordered: #unordered,
+                ignore_paths: ::std::vec![#(::std::string::String::from(#ignore_paths)),*],
+                redact: #redact,
                 _data_marker: ::core::default::Default::default(),
             }
         }
@@ -173,6 +281,13 @@ fn opt_string(opt: &Option<String>) -> proc_macro2::TokenStream {
     }
 }
 
+fn opt_f64(opt: &Option<f64>) -> proc_macro2::TokenStream {
+    match opt {
+        Some(v) => quote! { Some(#v) },
+        None => quote! { None },
+    }
+}
+
 fn opt_bool_default_false(opt: &Option<bool>) -> proc_macro2::TokenStream {
     match opt {
         Some(b) => quote! { #b },
@@ -180,6 +295,43 @@ fn opt_bool_default_false(opt: &Option<bool>) -> proc_macro2::TokenStream {
     }
 }
 
+/// Tokens constructing the `assert_tv::OffloadCodec` named by `"codec"` or `"codec:level"`.
+pub(crate) fn offload_codec_tokens(lit: &LitStr) -> syn::Result<proc_macro2::TokenStream> {
+    let value = lit.value();
+    let (codec, level) = match value.split_once(':') {
+        Some((codec, level)) => (codec, Some(level)),
+        None => (value.as_str(), None),
+    };
+    let invalid_level = || {
+        Error::new_spanned(
+            lit,
+            "invalid offload codec level, expected 1-22 for zstd and 0-9 for gzip",
+        )
+    };
+    match (codec, level) {
+        ("none", None) => Ok(quote! { ::assert_tv::OffloadCodec::None }),
+        ("zstd", None) => Ok(quote! { ::assert_tv::OffloadCodec::default() }),
+        ("zstd", Some(level)) => {
+            let level: i32 = level.parse().map_err(|_| invalid_level())?;
+            if !(1..=22).contains(&level) {
+                return Err(invalid_level());
+            }
+            Ok(quote! { ::assert_tv::OffloadCodec::Zstd { level: #level } })
+        }
+        ("gzip", level) => {
+            let level: u32 = level.unwrap_or("6").parse().map_err(|_| invalid_level())?;
+            if level > 9 {
+                return Err(invalid_level());
+            }
+            Ok(quote! { ::assert_tv::OffloadCodec::Gzip { level: #level } })
+        }
+        _ => Err(Error::new_spanned(
+            lit,
+            "invalid offload codec, expected none, zstd, zstd:<level>, gzip, gzip:<level>",
+        )),
+    }
+}
+
 /// Verify the field is of type `TestValue<...>`
 fn ensure_test_value_type(ty: &Type) -> syn::Result<()> {
     if let Type::Path(p) = ty {
@@ -194,25 +346,28 @@ fn ensure_test_value_type(ty: &Type) -> syn::Result<()> {
         "all fields in a TestVectorSet must be of type `TestValue<…>`",
     ))
 }
-/// Parses a single `#[test_vec(...)]` attribute using the syn 2 helper API.
+/// Parses a single `#[test_vec(...)]` attribute using the syn 2 helper API.
 ///
 /// Accepted keys are
 ///  * `name = "…"`,
 ///  * `description = "…"`,
 ///  * `serialize_with = "path::to::fn"`,
-///  * `deserialize_with = "path::to::fn"`.
+///  * `deserialize_with = "path::to::fn"`,
+///  * `encoding = "hex" | "base64"`,
+///  * `compare_with = "path::to::fn"`,
+///  * `compress = true|false`,
+///  * `offload = true|false`,
+///  * `offload_threshold = 65536`,
+///  * `offload_codec = "zstd" | "zstd:<level>" | "gzip" | "gzip:<level>" | "none"`,
+///  * `bytes` or `bytes = true|false`,
+///  * `tolerance = 1e-9`,
+///  * `rel_tolerance = 1e-9`,
+///  * `unordered`, `unordered = "$.path"` or `unordered = ["$.a", "$.b"]`,
+///  * `ignore_paths = "$.path"` or `ignore_paths = ["$.a", "$.b"]`,
+///  * `redact = "…"`.
 ///
 /// Any other key or any duplicate key results in a descriptive compile‑error.
-pub fn parse_test_vec_attribute(
-    attr: &Attribute,
-    name: &mut Option<String>,
-    description: &mut Option<String>,
-    serialize_with: &mut Option<syn::Path>,
-    deserialize_with: &mut Option<syn::Path>,
-    compress: &mut Option<bool>,
-    offload: &mut Option<bool>,
-) -> syn::Result<()> {
-    let _ = compress;
+fn parse_test_vec_attribute(attr: &Attribute, cfg: &mut FieldCfg) -> syn::Result<()> {
     attr.parse_nested_meta(|meta| {
         // ---- helper -------------------------------------------------------
         let get_lit_str = || -> syn::Result<LitStr> {
@@ -225,11 +380,39 @@ pub fn parse_test_vec_attribute(
                 .parse()
                 .map_err(|e: Error| Error::new(meta.path.span(), e.to_string()))
         };
+        let get_f64 = || -> syn::Result<f64> {
+            match meta.value()?.parse()? {
+                Lit::Float(lit) => lit.base10_parse(),
+                Lit::Int(lit) => lit.base10_parse(),
+                lit => Err(Error::new_spanned(lit, "expected a number literal")),
+            }
+        };
+        let get_paths = || -> syn::Result<Vec<String>> {
+            match meta.value()?.parse()? {
+                Expr::Lit(ExprLit {
+                    lit: Lit::Str(lit), ..
+                }) => Ok(vec![lit.value()]),
+                Expr::Array(array) => array
+                    .elems
+                    .iter()
+                    .map(|elem| match elem {
+                        Expr::Lit(ExprLit {
+                            lit: Lit::Str(lit), ..
+                        }) => Ok(lit.value()),
+                        elem => Err(Error::new_spanned(elem, "expected a JSON path string")),
+                    })
+                    .collect(),
+                expr => Err(Error::new_spanned(
+                    expr,
+                    "expected a JSON path string or an array of them",
+                )),
+            }
+        };
         // -------------------------------------------------------------------
 
         if meta.path.is_ident("name") {
             let lit: LitStr = get_lit_str()?;
-            if name.replace(lit.value()).is_some() {
+            if cfg.name.replace(lit.value()).is_some() {
                 return Err(meta.error("duplicate `name` key"));
             }
             return Ok(());
@@ -237,7 +420,7 @@ pub fn parse_test_vec_attribute(
 
         if meta.path.is_ident("description") {
             let lit: LitStr = get_lit_str()?;
-            if description.replace(lit.value()).is_some() {
+            if cfg.description.replace(lit.value()).is_some() {
                 return Err(meta.error("duplicate `description` key"));
             }
             return Ok(());
@@ -246,7 +429,7 @@ pub fn parse_test_vec_attribute(
         if meta.path.is_ident("serialize_with") {
             let lit: LitStr = get_lit_str()?;
             let path: syn::Path = syn::parse_str(&lit.value())?;
-            if serialize_with.replace(path).is_some() {
+            if cfg.serialize_with.replace(path).is_some() {
                 return Err(meta.error("duplicate `serialize_with` key"));
             }
             return Ok(());
@@ -255,28 +438,198 @@ pub fn parse_test_vec_attribute(
         if meta.path.is_ident("deserialize_with") {
             let lit: LitStr = get_lit_str()?;
             let path: syn::Path = syn::parse_str(&lit.value())?;
-            if deserialize_with.replace(path).is_some() {
+            if cfg.deserialize_with.replace(path).is_some() {
                 return Err(meta.error("duplicate `deserialize_with` key"));
             }
             return Ok(());
         }
 
-        // if meta.path.is_ident("compress") {
-        //     let lit: LitBool = get_lit_bool()?;
-        //     if compress.replace(lit.value()).is_some() {
-        //         return Err(meta.error("duplicate `compress` key"));
-        //     }
-        // }
+        if meta.path.is_ident("encoding") {
+            let lit: LitStr = get_lit_str()?;
+            let encoding = match lit.value().as_str() {
+                "hex" | "base64" => syn::Ident::new(&lit.value(), lit.span()),
+                _ => return Err(Error::new_spanned(lit, "invalid encoding, expected hex or base64")),
+            };
+            if cfg.encoding.replace(encoding).is_some() {
+                return Err(meta.error("duplicate `encoding` key"));
+            }
+            return Ok(());
+        }
+
+        if meta.path.is_ident("compare_with") {
+            let lit: LitStr = get_lit_str()?;
+            let path: syn::Path = syn::parse_str(&lit.value())?;
+            if cfg.compare_with.replace(path).is_some() {
+                return Err(meta.error("duplicate `compare_with` key"));
+            }
+            return Ok(());
+        }
+
+        if meta.path.is_ident("compress") {
+            let lit: LitBool = get_lit_bool()?;
+            if cfg.compress.replace(lit.value()).is_some() {
+                return Err(meta.error("duplicate `compress` key"));
+            }
+            return Ok(());
+        }
+
         if meta.path.is_ident("offload") {
             let lit: LitBool = get_lit_bool()?;
-            if offload.replace(lit.value()).is_some() {
+            if cfg.offload.replace(lit.value()).is_some() {
                 return Err(meta.error("duplicate `offload` key"));
             }
             return Ok(());
         }
 
+        if meta.path.is_ident("offload_threshold") {
+            let lit: LitInt = meta.value()?.parse()?;
+            if cfg.offload_threshold.replace(lit.base10_parse()?).is_some() {
+                return Err(meta.error("duplicate `offload_threshold` key"));
+            }
+            return Ok(());
+        }
+
+        if meta.path.is_ident("offload_codec") {
+            let lit: LitStr = get_lit_str()?;
+            if cfg
+                .offload_codec
+                .replace(offload_codec_tokens(&lit)?)
+                .is_some()
+            {
+                return Err(meta.error("duplicate `offload_codec` key"));
+            }
+            return Ok(());
+        }
+
+        if meta.path.is_ident("bytes") {
+            let value = if meta.input.peek(Token![=]) {
+                get_lit_bool()?.value()
+            } else {
+                true
+            };
+            if cfg.bytes.replace(value).is_some() {
+                return Err(meta.error("duplicate `bytes` key"));
+            }
+            return Ok(());
+        }
+
+        if meta.path.is_ident("tolerance") {
+            let value = get_f64()?;
+            if cfg.tolerance.replace(value).is_some() {
+                return Err(meta.error("duplicate `tolerance` key"));
+            }
+            return Ok(());
+        }
+
+        if meta.path.is_ident("rel_tolerance") {
+            let value = get_f64()?;
+            if cfg.rel_tolerance.replace(value).is_some() {
+                return Err(meta.error("duplicate `rel_tolerance` key"));
+            }
+            return Ok(());
+        }
+
+        if meta.path.is_ident("unordered") {
+            // a bare `unordered` selects every array of the value
+            let paths = if meta.input.peek(Token![=]) {
+                get_paths()?
+            } else {
+                Vec::new()
+            };
+            if cfg.unordered.replace(paths).is_some() {
+                return Err(meta.error("duplicate `unordered` key"));
+            }
+            return Ok(());
+        }
+
+        if meta.path.is_ident("ignore_paths") {
+            let paths = get_paths()?;
+            if cfg.ignore_paths.replace(paths).is_some() {
+                return Err(meta.error("duplicate `ignore_paths` key"));
+            }
+            return Ok(());
+        }
+
+        if meta.path.is_ident("redact") {
+            let lit: LitStr = get_lit_str()?;
+            if cfg.redact.replace(lit.value()).is_some() {
+                return Err(meta.error("duplicate `redact` key"));
+            }
+            return Ok(());
+        }
+
         Err(meta.error(
-            "unrecognised key; allowed: name, description, serialize_with, deserialize_with, offload",
+            "unrecognised key; allowed: name, description, serialize_with, deserialize_with, encoding, compare_with, compress, offload, offload_threshold, offload_codec, bytes, tolerance, rel_tolerance, unordered, ignore_paths, redact",
         ))
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{expand, offload_codec_tokens};
+    use syn::parse_quote;
+
+    fn expand_error(input: syn::DeriveInput) -> String {
+        expand(&input).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn compare_with_rejects_structural_options() {
+        let errors = [
+            expand_error(parse_quote! {
+                struct Fields {
+                    #[test_vec(compare_with = "cmp", ignore_paths = ["$.id"])]
+                    response: TestValue<Response>,
+                }
+            }),
+            expand_error(parse_quote! {
+                struct Fields {
+                    #[test_vec(compare_with = "cmp", tolerance = 1e-9)]
+                    response: TestValue<Response>,
+                }
+            }),
+            expand_error(parse_quote! {
+                struct Fields {
+                    #[test_vec(compare_with = "cmp", rel_tolerance = 1e-9)]
+                    response: TestValue<Response>,
+                }
+            }),
+            expand_error(parse_quote! {
+                struct Fields {
+                    #[test_vec(compare_with = "cmp", unordered)]
+                    response: TestValue<Response>,
+                }
+            }),
+        ];
+        for (error, key) in
+            errors
+                .iter()
+                .zip(["ignore_paths", "tolerance", "rel_tolerance", "unordered"])
+        {
+            assert!(
+                error.contains(&format!("cannot be combined with `{key}`")),
+                "{error}"
+            );
+        }
+    }
+
+    #[test]
+    fn offload_codec_levels_are_range_checked() {
+        for codec in [
+            "none", "zstd", "zstd:1", "zstd:22", "gzip", "gzip:0", "gzip:9",
+        ] {
+            assert!(
+                offload_codec_tokens(&parse_quote!(#codec)).is_ok(),
+                "{codec}"
+            );
+        }
+        for codec in [
+            "zstd:0", "zstd:99", "zstd:-1", "gzip:10", "gzip:42", "gzip:-1",
+        ] {
+            assert!(
+                offload_codec_tokens(&parse_quote!(#codec)).is_err(),
+                "{codec}"
+            );
+        }
+    }
+}
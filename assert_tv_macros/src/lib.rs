@@ -6,7 +6,7 @@ use std::borrow::Borrow;
 use proc_macro::TokenStream;
 use quote::quote;
 use syn::punctuated::Punctuated;
-use syn::{parse_macro_input, DeriveInput, Error, Expr, ExprLit, ItemFn, Lit, Meta, Token};
+use syn::{parse_macro_input, DeriveInput, Error, Expr, ExprLit, ItemFn, Lit, LitStr, Meta, Token};
 
 /// Derive `assert_tv::TestVectorSet` for a struct of `TestValue<…>` fields.
 ///
@@ -19,7 +19,18 @@ use syn::{parse_macro_input, DeriveInput, Error, Expr, ExprLit, ItemFn, Lit, Met
 /// - `description = "…"`: longer description for reports.
 /// - `serialize_with = "path::to::fn"`: `fn(&T) -> anyhow::Result<serde_json::Value>`.
 /// - `deserialize_with = "path::to::fn"`: `fn(&serde_json::Value) -> anyhow::Result<T>`.
-/// - `offload = true`: store value in a compressed sidecar file.
+/// - `encoding = "hex" | "base64"`: store a byte field (`Vec<u8>`, `[u8; N]`, …) as a string instead of an array of integers.
+/// - `compare_with = "path::to::fn"`: `fn(&T, &T) -> bool` deciding if loaded and observed outputs match; not combinable with `ignore_paths`.
+/// - `compress = true`: store value inline as base64 encoded zstd.
+/// - `offload = true`: store value in a compressed blob file next to the test vector file.
+/// - `offload_threshold = 65536`: offload the value only if its serialized JSON is longer.
+/// - `offload_codec = "zstd:3"`: codec of the offloaded value (`none`, `zstd[:level]`, `gzip[:level]`).
+/// - `bytes`: offload the value as raw bytes; it must serialize to an array of integers in `0..=255`
+///   (offloaded values of that shape are stored as raw bytes even without it).
+/// - `tolerance = 1e-9` / `rel_tolerance = 1e-6`: numeric tolerances for checking outputs.
+/// - `unordered` / `unordered = ["$.path", …]`: sort all (or the selected) arrays of an output before storing and comparing.
+/// - `ignore_paths = ["$.path", …]`: store the selected parts of an output as a placeholder and skip them when checking.
+/// - `redact = "…"`: placeholder stored for `ignore_paths` (default `"<ignored>"`).
 ///
 /// Example:
 /// ```rust,ignore
@@ -44,6 +55,25 @@ pub fn derive_test_vector_set(input: TokenStream) -> TokenStream {
     }
 }
 
+/// The `TestVectorFileFormat` and default file extension named by a `format` argument or
+/// a file extension.
+fn file_format_tokens(name: &str) -> Option<(&'static str, proc_macro2::TokenStream)> {
+    let file_format = match name {
+        "yaml" | "yml" => ("yaml", quote! {assert_tv::TestVectorFileFormat::Yaml}),
+        "json" => ("json", quote! {assert_tv::TestVectorFileFormat::Json}),
+        "toml" => ("toml", quote! {assert_tv::TestVectorFileFormat::Toml}),
+        "ron" => ("ron", quote! {assert_tv::TestVectorFileFormat::Ron}),
+        "jsonl" | "jsonlines" => ("jsonl", quote! {assert_tv::TestVectorFileFormat::JsonLines}),
+        "cbor" => ("cbor", quote! {assert_tv::TestVectorFileFormat::Cbor}),
+        "msgpack" | "messagepack" => (
+            "msgpack",
+            quote! {assert_tv::TestVectorFileFormat::MessagePack},
+        ),
+        _ => return None,
+    };
+    Some(file_format)
+}
+
 /// Attribute macro for tests that use assert_tv test vectors.
 ///
 /// Wraps a `#[test]` function with automatic initialization/finalization of a
@@ -51,8 +81,25 @@ pub fn derive_test_vector_set(input: TokenStream) -> TokenStream {
 ///
 /// Arguments:
 /// - `file = "path/to/file.ext"` (optional): defaults to `.test_vectors/<fn_name>.<format>`.
-/// - `format = "json" | "yaml" | "toml"` (optional): defaults to `"json"`.
-/// - `mode = "init" | "check"` (optional): defaults to `TEST_MODE` env var, else `"check"`.
+/// - `format = "json" | "yaml" | "toml" | "ron" | "jsonl" | "cbor" | "msgpack"` (optional):
+///   defaults to the format named by the extension of `file` (an unknown extension is a
+///   compile error), else `"json"`. `ron`, `cbor` and `msgpack` require the features of the same name
+///   of `assert_tv`.
+/// - `mode = "init" | "check" | "update" | "auto"` (optional): defaults to `TEST_MODE` env var,
+///   else `"check"`. `"auto"` checks an existing file and initializes a missing one.
+/// - `matching = "positional" | "keyed"` (optional): how observed entries are paired with
+///   loaded entries in check mode; defaults to `"positional"`.
+/// - `fail = "immediate" | "deferred"` (optional): with `"deferred"`, mismatching outputs
+///   are collected and reported together when the test finishes; defaults to `"immediate"`.
+/// - `allow_unconsumed = true` (optional): do not fail in check mode when the test
+///   stops before replaying every loaded entry.
+/// - `offload_threshold = 65536` (optional): offload every entry whose serialized JSON is
+///   longer than this many bytes.
+/// - `offload_codec = "zstd:3"` (optional): codec of offloaded values, one of `none`,
+///   `zstd[:level]` or `gzip[:level]` (requires the `gzip` feature); defaults to zstd level 15.
+/// - `streaming = true` (optional): append entries to disk while recording and read them
+///   one at a time while checking, instead of keeping the vector in memory. Requires a
+///   `jsonl` file and positional matching.
 ///
 /// Example:
 /// ```rust,ignore
@@ -63,7 +110,7 @@ pub fn derive_test_vector_set(input: TokenStream) -> TokenStream {
 ///     // uses .test_vectors/my_default_case.json
 /// }
 ///
-/// #[test_vec_case(file = "tests/vecs/case.yaml", format = "yaml", mode = "init")]
+/// #[test_vec_case(file = "tests/vecs/case.yaml", mode = "init")]
 /// fn my_yaml_init_case() {
 ///     // initializes YAML vectors at the given path
 /// }
@@ -77,10 +124,15 @@ pub fn test_vec_case(attr: TokenStream, item: TokenStream) -> TokenStream {
     let fn_block = &input.block;
     let attrs = &input.attrs;
 
-    let mut file_path: Option<String> = None;
-    let mut file_format_ending: &'static str = "json";
-    let mut file_format_quoted = quote! {assert_tv::TestVectorFileFormat::Json};
+    let mut file_path: Option<LitStr> = None;
+    let mut file_format: Option<(&'static str, proc_macro2::TokenStream)> = None;
     let mut test_mode = quote! { assert_tv::TestMode::from_environment() };
+    let mut allow_unconsumed = false;
+    let mut streaming = false;
+    let mut matching = quote! { assert_tv::MatchingStrategy::Positional };
+    let mut failure_mode = quote! { assert_tv::FailureMode::Immediate };
+    let mut offload_threshold = quote! { None };
+    let mut offload_codec = quote! { ::core::default::Default::default() };
 
     // Process attribute arguments
     for meta in args {
@@ -106,7 +158,7 @@ pub fn test_vec_case(attr: TokenStream, item: TokenStream) -> TokenStream {
                     lit: Lit::Str(v), ..
                 }),
             ) => {
-                file_path = Some(v.value());
+                file_path = Some(v.clone());
             }
 
             (
@@ -117,14 +169,12 @@ pub fn test_vec_case(attr: TokenStream, item: TokenStream) -> TokenStream {
                     },
                 ),
             ) => {
-                (file_format_ending, file_format_quoted) = match val.value().as_str() {
-                    "yaml" | "yml" => ("yaml", quote! {assert_tv::TestVectorFileFormat::Yaml}),
-                    "json" => ("json", quote! {assert_tv::TestVectorFileFormat::Json}),
-                    "toml" => ("toml", quote! {assert_tv::TestVectorFileFormat::Toml}),
-                    _ => {
+                file_format = match file_format_tokens(&val.value()) {
+                    Some(file_format) => Some(file_format),
+                    None => {
                         return Error::new_spanned(
                             lit_str,
-                            "invalid format, expected yaml/yml or json",
+                            "invalid format, expected json, yaml/yml, toml, ron, jsonl, cbor or msgpack",
                         )
                         .to_compile_error()
                         .into();
@@ -143,15 +193,119 @@ pub fn test_vec_case(attr: TokenStream, item: TokenStream) -> TokenStream {
                 test_mode = match val.value().as_str() {
                     "init" => quote! {assert_tv::TestMode::Init},
                     "check" => quote! {assert_tv::TestMode::Check},
+                    "update" => quote! {assert_tv::TestMode::Update},
+                    "auto" => quote! {assert_tv::TestMode::InitIfMissing},
+                    _ => {
+                        return Error::new_spanned(
+                            lit_str,
+                            "invalid mode, expected init, check, update, auto",
+                        )
+                        .to_compile_error()
+                        .into();
+                    }
+                };
+            }
+
+            (
+                "matching",
+                Expr::Lit(
+                    lit_str @ ExprLit {
+                        lit: Lit::Str(val), ..
+                    },
+                ),
+            ) => {
+                matching = match val.value().as_str() {
+                    "positional" => quote! {assert_tv::MatchingStrategy::Positional},
+                    "keyed" => quote! {assert_tv::MatchingStrategy::Keyed},
                     _ => {
-                        return Error::new_spanned(lit_str, "invalid format, expected init, check")
-                            .to_compile_error()
-                            .into();
+                        return Error::new_spanned(
+                            lit_str,
+                            "invalid matching, expected positional, keyed",
+                        )
+                        .to_compile_error()
+                        .into();
                     }
                 };
             }
 
-            ("file" | "format" | "mode", nv_value) => {
+            (
+                "fail",
+                Expr::Lit(
+                    lit_str @ ExprLit {
+                        lit: Lit::Str(val), ..
+                    },
+                ),
+            ) => {
+                failure_mode = match val.value().as_str() {
+                    "immediate" => quote! {assert_tv::FailureMode::Immediate},
+                    "deferred" => quote! {assert_tv::FailureMode::Deferred},
+                    _ => {
+                        return Error::new_spanned(
+                            lit_str,
+                            "invalid fail mode, expected immediate, deferred",
+                        )
+                        .to_compile_error()
+                        .into();
+                    }
+                };
+            }
+
+            (
+                "allow_unconsumed",
+                Expr::Lit(ExprLit {
+                    lit: Lit::Bool(v), ..
+                }),
+            ) => {
+                allow_unconsumed = v.value();
+            }
+
+            (
+                "streaming",
+                Expr::Lit(ExprLit {
+                    lit: Lit::Bool(v), ..
+                }),
+            ) => {
+                streaming = v.value();
+            }
+
+            (
+                "offload_threshold",
+                Expr::Lit(ExprLit {
+                    lit: Lit::Int(v), ..
+                }),
+            ) => {
+                let threshold = match v.base10_parse::<usize>() {
+                    Ok(threshold) => threshold,
+                    Err(e) => return e.to_compile_error().into(),
+                };
+                offload_threshold = quote! { Some(#threshold) };
+            }
+
+            (
+                "offload_codec",
+                Expr::Lit(ExprLit {
+                    lit: Lit::Str(v), ..
+                }),
+            ) => {
+                offload_codec = match derive::offload_codec_tokens(v) {
+                    Ok(codec) => codec,
+                    Err(e) => return e.to_compile_error().into(),
+                };
+            }
+
+            ("offload_threshold", nv_value) => {
+                return Error::new_spanned(nv_value, "expected integer literal")
+                    .to_compile_error()
+                    .into();
+            }
+
+            ("allow_unconsumed" | "streaming", nv_value) => {
+                return Error::new_spanned(nv_value, "expected bool literal")
+                    .to_compile_error()
+                    .into();
+            }
+
+            ("file" | "format" | "mode" | "matching" | "fail" | "offload_codec", nv_value) => {
                 return Error::new_spanned(nv_value, "expected string literal")
                     .to_compile_error()
                     .into();
@@ -164,8 +318,30 @@ pub fn test_vec_case(attr: TokenStream, item: TokenStream) -> TokenStream {
             }
         }
     }
+    let (file_format_ending, file_format_quoted) = match (file_format, &file_path) {
+        (Some(file_format), _) => file_format,
+        // without an explicit `format`, the extension of `file` decides
+        (None, Some(file_path)) => {
+            let extension = std::path::Path::new(&file_path.value())
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .map(str::to_owned);
+            match extension.as_deref().and_then(file_format_tokens) {
+                Some(file_format) => file_format,
+                None => {
+                    return Error::new_spanned(
+                        file_path,
+                        "cannot infer the format from the file extension; use .json, .yaml, .yml, .toml, .ron, .jsonl, .cbor or .msgpack, or set `format`",
+                    )
+                    .to_compile_error()
+                    .into();
+                }
+            }
+        }
+        (None, None) => ("json", quote! {assert_tv::TestVectorFileFormat::Json}),
+    };
     let file_path: String = match file_path {
-        Some(file_path) => file_path,
+        Some(file_path) => file_path.value(),
         None => {
             // default file path is derived from the test function name and is put under .test_vectors/
             let default_file = format!(".test_vectors/{fn_name}.{file_format_ending}");
@@ -179,12 +355,30 @@ pub fn test_vec_case(attr: TokenStream, item: TokenStream) -> TokenStream {
         #(#attrs)*
         #[test]
         fn #fn_name() #fn_result {
-            let _guard = assert_tv::initialize_tv_case_from_file(#file_path, #file_format_quoted, #test_mode)
-                .expect("Error initializing test vector case");
+            #[allow(clippy::needless_update)]
+            let _guard = assert_tv::initialize_tv_case_with_options(
+                #file_path,
+                #file_format_quoted,
+                #test_mode,
+                assert_tv::TestVectorOptions {
+                    allow_unconsumed: #allow_unconsumed,
+                    matching: #matching,
+                    failure_mode: #failure_mode,
+                    offload_threshold: #offload_threshold,
+                    offload_codec: #offload_codec,
+                    streaming: #streaming,
+                    package_name: ::core::option::Option::Some(::std::string::String::from(::core::env!("CARGO_PKG_NAME"))),
+                    package_version: ::core::option::Option::Some(::std::string::String::from(::core::env!("CARGO_PKG_VERSION"))),
+                    ..::core::default::Default::default()
+                },
+            )
+            .unwrap_or_else(|e| panic!("Error initializing test vector case: {e}"));
+            #[allow(clippy::diverging_sub_expression)]
             let result = #fn_block;
             #[allow(unreachable_code)]
             {
-                assert_tv::finalize_tv_case().expect("Error finalizing test vector case");
+                assert_tv::finalize_tv_case()
+                    .unwrap_or_else(|e| panic!("Error finalizing test vector case: {e}"));
                 drop(_guard);
                 result
             }